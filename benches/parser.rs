@@ -0,0 +1,82 @@
+#[macro_use]
+extern crate criterion;
+extern crate multiwii_serial_protocol_v2;
+
+use criterion::{Criterion, Throughput};
+use multiwii_serial_protocol_v2::{MspPacket, MspPacketDirection, MspParser, MspVersion};
+
+/// Back to back V2 telemetry frames, as streamed by a flight controller
+fn telemetry_stream() -> Vec<u8> {
+    let mut stream = vec![];
+    for (cmd, len) in &[(102, 18), (105, 36), (108, 6), (130, 11), (0x2000, 128)] {
+        let packet = MspPacket {
+            cmd: *cmd,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: (0..*len).map(|i| i as u8).collect(),
+        };
+        stream.extend(Vec::from(packet));
+    }
+    stream.repeat(200)
+}
+
+fn parse_v2(c: &mut Criterion) {
+    let stream = telemetry_stream();
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+
+    group.bench_function("feed_v2", |b| {
+        let mut parser = MspParser::new();
+        b.iter(|| {
+            let mut count = 0;
+            parser.feed(&stream, |_| count += 1).unwrap();
+            count
+        })
+    });
+
+    group.bench_function("parse_v2", |b| {
+        let mut parser = MspParser::new();
+        b.iter(|| {
+            let mut count = 0;
+            for byte in &stream {
+                if parser.parse(*byte).unwrap().is_some() {
+                    count += 1;
+                }
+            }
+            count
+        })
+    });
+
+    group.finish();
+}
+
+/// A long run of line noise ahead of a single frame, as seen when attaching to a busy UART
+fn noisy_stream() -> Vec<u8> {
+    let mut stream: Vec<u8> = (0..4096u32).map(|i| (i * 7 % 251) as u8 + 1).collect();
+    stream.retain(|b| *b != b'$');
+    stream.extend(Vec::from(
+        MspPacket::request(108).with_payload(vec![1, 2, 3]),
+    ));
+    stream
+}
+
+fn parse_noise(c: &mut Criterion) {
+    let stream = noisy_stream();
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+
+    group.bench_function("leading_noise", |b| {
+        let mut parser = MspParser::new();
+        b.iter(|| {
+            let mut count = 0;
+            parser.feed(&stream, |_| count += 1).unwrap();
+            count
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse_v2, parse_noise);
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+//! `quickcheck::Arbitrary` implementations, generating packets that can be framed and parsed
+//! back unchanged
+
+use prelude::v1::*;
+use quickcheck::{Arbitrary, Gen};
+
+use packet::{MspPacket, MspPacketDirection, MspVersion};
+
+impl Arbitrary for MspPacketDirection {
+    fn arbitrary(g: &mut Gen) -> Self {
+        *g.choose(&[
+            MspPacketDirection::ToFlightController,
+            MspPacketDirection::FromFlightController,
+            MspPacketDirection::Unsupported,
+        ])
+        .unwrap()
+    }
+}
+
+impl Arbitrary for MspVersion {
+    fn arbitrary(g: &mut Gen) -> Self {
+        *g.choose(&[MspVersion::V1, MspVersion::V2]).unwrap()
+    }
+}
+
+/// A V1 packet has a command below the V2 tunnel command 255, a payload of at most 255 bytes
+/// and no flag. A V2 packet may use any command and flag.
+impl Arbitrary for MspPacket {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let version = MspVersion::arbitrary(g);
+        let mut data = Vec::<u8>::arbitrary(g);
+        let (cmd, flag) = match version {
+            MspVersion::V1 => {
+                data.truncate(0xff);
+                (u16::from(u8::arbitrary(g) % 0xff), 0)
+            }
+            MspVersion::V2 => (u16::arbitrary(g), u8::arbitrary(g)),
+        };
+
+        MspPacket {
+            cmd,
+            direction: MspPacketDirection::arbitrary(g),
+            version,
+            flag,
+            data,
+        }
+    }
+
+    /// Only the payload is shrunk, which keeps the packet valid
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let packet = self.clone();
+        Box::new(self.data.shrink().map(move |data| MspPacket {
+            data,
+            ..packet.clone()
+        }))
+    }
+}
+
+#[cfg(test)]
+fn roundtrip(packet: MspPacket) -> bool {
+    let mut frame = vec![];
+    match packet.version {
+        MspVersion::V1 => packet.serialize_into(&mut frame),
+        MspVersion::V2 => packet.serialize_v2_into(&mut frame),
+    }
+    .unwrap();
+
+    MspPacket::parse_one(&frame) == Ok(packet)
+}
+
+#[test]
+fn test_roundtrip_property() {
+    ::quickcheck::QuickCheck::new()
+        .tests(1000)
+        .quickcheck(roundtrip as fn(MspPacket) -> bool);
+}
+
+#[test]
+fn test_roundtrip_framing_bytes() {
+    // payloads made of header bytes only, which the parser must not resync on
+    let mut g = Gen::new(64);
+    for _ in 0..200 {
+        let mut packet = MspPacket::arbitrary(&mut g);
+        for b in packet.data.iter_mut() {
+            *b = *g.choose(b"$MX<>!").unwrap();
+        }
+        assert!(roundtrip(packet.clone()), "{}", packet);
+    }
+}
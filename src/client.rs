@@ -0,0 +1,1263 @@
+//! Blocking request/response client
+
+use prelude::v1::*;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use packet::{MspPacket, MspPacketDirection, MspPacketParseError, MspParser, MspVersion};
+use payload::MspPayload;
+
+/// Blocking MSP client over a `Read + Write` transport, such as a serial port.
+///
+/// Waiting for a response is bounded by two timeouts. The response timeout caps the total time
+/// spent on one request, while the read timeout caps how long the link may stay idle between
+/// received bytes. A slow link that keeps trickling bytes is given the full response timeout,
+/// but a silent one is given up on as soon as the read timeout passes.
+///
+/// An optional idle reset drops a partially received frame once the link has been silent for
+/// that long, so noise on a half-duplex link can not swallow the start of the next real frame.
+///
+/// An optional `MspObserver` is told about every request, response, timeout and skipped corrupt
+/// frame, such as for counting them.
+pub struct MspClient<T: Read + Write> {
+    transport: T,
+    version: MspVersion,
+    parser: MspParser,
+    /// Scratch buffer the requests are serialized into, reused for every request
+    write_buf: Vec<u8>,
+    read_timeout: Duration,
+    response_timeout: Duration,
+    idle_reset: Option<Duration>,
+    observer: Option<Arc<dyn MspObserver>>,
+    clock: &'static dyn Clock,
+}
+
+/// Hooks into the requests of an `MspClient`. Every method does nothing by default.
+pub trait MspObserver: Send + Sync {
+    /// `request` was written to the transport
+    fn on_request(&self, _request: &MspPacket) {}
+
+    /// `response` matched the request waiting for it
+    fn on_response(&self, _response: &MspPacket) {}
+
+    /// No response to `request` arrived before a timeout expired
+    fn on_timeout(&self, _request: &MspPacket) {}
+
+    /// A corrupt frame was skipped while waiting for a response
+    fn on_parse_error(&self, _error: &MspPacketParseError) {}
+}
+
+impl<T: Read + Write> MspClient<T> {
+    /// Create a new client that frames its requests with the given protocol version
+    pub fn new(
+        transport: T,
+        version: MspVersion,
+        read_timeout: Duration,
+        response_timeout: Duration,
+    ) -> MspClient<T> {
+        MspClient {
+            transport,
+            version,
+            parser: MspParser::new(),
+            write_buf: Vec::new(),
+            read_timeout,
+            response_timeout,
+            idle_reset: None,
+            observer: None,
+            clock: DEFAULT_CLOCK,
+        }
+    }
+
+    /// Drop a partially received frame when no bytes arrive for `idle`. Only useful when
+    /// `idle` is shorter than the read timeout.
+    pub fn with_idle_reset(mut self, idle: Duration) -> MspClient<T> {
+        self.idle_reset = Some(idle);
+        self
+    }
+
+    /// Report requests, responses, timeouts and parse errors to `observer`
+    pub fn with_observer(mut self, observer: Arc<dyn MspObserver>) -> MspClient<T> {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Protocol version used to frame requests
+    pub fn version(&self) -> MspVersion {
+        self.version
+    }
+
+    /// Change the protocol version used to frame requests
+    pub fn set_version(&mut self, version: MspVersion) {
+        self.version = version;
+    }
+
+    /// Consume the client, returning the underlying transport
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Send a request and wait for the flight controller's response to the same command.
+    ///
+    /// Unrelated packets and corrupt frames are skipped while waiting. Both timeouts are only
+    /// checked between reads, so the transport needs its own, shorter read timeout for this to
+    /// return on a silent link; `WouldBlock` and `TimedOut` read errors are treated as "no data
+    /// yet". Either timeout expiring fails with `Io(TimedOut)`.
+    ///
+    /// A V1 client sends commands above 255, and requests tagged with a sequence, as V2 frames
+    /// tunneled in V1, see `MspPacket::tunnel_v1`. A plain V1 frame has no room for either.
+    ///
+    /// A flight controller that does not implement the command replies with an unsupported (`!`)
+    /// response, which fails with `UnsupportedCommand`.
+    ///
+    /// A partially received frame left over from an earlier request, such as one that timed out,
+    /// is dropped before waiting, so it can not swallow the start of the response.
+    ///
+    /// A request tagged with `MspPacket::with_sequence` only matches a response echoing the
+    /// same sequence, so a late reply to an earlier request for the same command is skipped.
+    ///
+    /// Anything convertible into a packet is accepted, so `client.request(101)` sends an empty
+    /// `MSP_STATUS` request.
+    pub fn request<P: Into<MspPacket>>(
+        &mut self,
+        packet: P,
+    ) -> Result<MspPacket, MspPacketParseError> {
+        let (read_timeout, response_timeout) = (self.read_timeout, self.response_timeout);
+        self.request_within(packet.into(), read_timeout, response_timeout)
+    }
+
+    /// Same as `request`, but wait up to `timeout` for this one response in place of both the
+    /// read and the response timeout. Slow commands such as `MSP_EEPROM_WRITE` leave the link
+    /// silent for longer than the read timeout, while a telemetry poll may want to give up
+    /// sooner.
+    pub fn request_with_timeout<P: Into<MspPacket>>(
+        &mut self,
+        packet: P,
+        timeout: Duration,
+    ) -> Result<MspPacket, MspPacketParseError> {
+        self.request_within(packet.into(), timeout, timeout)
+    }
+
+    /// Same as `request`, but send the request again, up to `retries` more times, while it times
+    /// out, as radio links drop packets. Any other error is returned straight away.
+    pub fn request_with_retries<P: Into<MspPacket>>(
+        &mut self,
+        packet: P,
+        retries: u8,
+    ) -> Result<MspPacket, MspPacketParseError> {
+        let packet = packet.into();
+        let mut result = self.request(packet.clone());
+        for _ in 0..retries {
+            match result {
+                Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)) => {
+                    result = self.request(packet.clone())
+                }
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// Send `packet` and wait for the response to it, bounded by the given timeouts
+    fn request_within(
+        &mut self,
+        packet: MspPacket,
+        read_timeout: Duration,
+        response_timeout: Duration,
+    ) -> Result<MspPacket, MspPacketParseError> {
+        let deadline = self.clock.now() + response_timeout;
+
+        match self.version {
+            MspVersion::V1 if packet.cmd > 0xff || packet.flag != 0 => {
+                packet.tunnel_v1()?.serialize_into(&mut self.write_buf)?
+            }
+            MspVersion::V1 => packet.serialize_into(&mut self.write_buf)?,
+            MspVersion::V2 => packet.serialize_v2_into(&mut self.write_buf)?,
+        }
+        self.transport.write_all(&self.write_buf)?;
+        self.transport.flush()?;
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(
+            cmd = packet.cmd,
+            version = ?self.version,
+            len = packet.data.len(),
+            "msp request sent"
+        );
+        if let Some(ref observer) = self.observer {
+            observer.on_request(&packet);
+        }
+
+        self.parser.reset();
+        let mut buf = [0; 64];
+        let mut last_read = self.clock.now();
+        loop {
+            let time = self.clock.now();
+            if time >= deadline || time - last_read >= read_timeout {
+                if let Some(ref observer) = self.observer {
+                    observer.on_timeout(&packet);
+                }
+                return Err(MspPacketParseError::Io(io::ErrorKind::TimedOut));
+            }
+
+            let n = match self.transport.read(&mut buf) {
+                Ok(0) => return Err(MspPacketParseError::Io(io::ErrorKind::UnexpectedEof)),
+                Ok(n) => {
+                    let now = self.clock.now();
+                    if let Some(idle) = self.idle_reset {
+                        if now - last_read >= idle && !self.parser.state_is_between_packets() {
+                            #[cfg(feature = "tracing")]
+                            ::tracing::debug!("msp partial frame dropped after idle link");
+                            self.parser.reset();
+                        }
+                    }
+                    last_read = now;
+                    n
+                }
+                Err(ref e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::TimedOut
+                            | io::ErrorKind::Interrupted
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            for b in &buf[..n] {
+                match self.parser.parse(*b) {
+                    Ok(Some(response)) => {
+                        if packet.same_transaction(&response)
+                            && response.direction != MspPacketDirection::ToFlightController
+                        {
+                            #[cfg(feature = "tracing")]
+                            ::tracing::debug!(
+                                cmd = response.cmd,
+                                direction = ?response.direction,
+                                len = response.data.len(),
+                                "msp response matched"
+                            );
+                            if let Some(ref observer) = self.observer {
+                                observer.on_response(&response);
+                            }
+                            if response.is_error() {
+                                return Err(MspPacketParseError::UnsupportedCommand {
+                                    cmd: response.cmd,
+                                });
+                            }
+                            return Ok(response);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(ref e) => {
+                        if let Some(ref observer) = self.observer {
+                            observer.on_parse_error(e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Request a `P` from the flight controller and decode its response, for commands whose
+    /// request carries no payload, as in `let api: MspApiVersion = client.request_typed()?`.
+    /// A flight controller that does not implement the command fails with
+    /// `UnsupportedCommand`.
+    pub fn request_typed<P: MspPayload>(&mut self) -> Result<P, MspPacketParseError> {
+        self.request(P::COMMAND)?.payload_as()
+    }
+
+    /// Send the typed request `req` and decode the response to it as an `R`, for commands whose
+    /// request and response payloads differ, such as `MSP2_COMMON_SETTING` sending a setting name
+    /// and answering with its value. The response must carry the command of `Q`; `R` only
+    /// describes its payload. A flight controller that does not implement the command fails with
+    /// `UnsupportedCommand`.
+    pub fn request_typed_with<Q: MspPayload, R: MspPayload>(
+        &mut self,
+        req: &Q,
+    ) -> Result<R, MspPacketParseError> {
+        let response = self.request((Q::COMMAND, req.encode()?))?;
+        if response.cmd != Q::COMMAND {
+            return Err(MspPacketParseError::WrongCommand {
+                expected: Q::COMMAND,
+                actual: response.cmd,
+            });
+        }
+
+        R::decode(&response.data)
+    }
+}
+
+/// Time source of the client's timeouts
+trait Clock: Sync {
+    fn now(&self) -> Instant;
+}
+
+#[cfg_attr(test, allow(dead_code))]
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock of new clients, the simulated one of the mock transports under test
+#[cfg(not(test))]
+const DEFAULT_CLOCK: &dyn Clock = &SystemClock;
+#[cfg(test)]
+const DEFAULT_CLOCK: &dyn Clock = &SimulatedClock;
+
+/// What `ReconnectingMspClient` does with a request whose link failed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReconnectPolicy {
+    /// Reconnect straight away and send the request once more
+    Retry,
+    /// Fail the request, and reconnect on the next one
+    Fail,
+}
+
+/// An `MspClient` that replaces its transport when the link fails, such as when a USB flight
+/// controller re-enumerates after a reboot. `connect` builds a fresh client; it is called on
+/// the first request, and again after any I/O error other than a timeout, which only means the
+/// flight controller did not answer.
+pub struct ReconnectingMspClient<T: Read + Write, F> {
+    connect: F,
+    client: Option<MspClient<T>>,
+    policy: ReconnectPolicy,
+}
+
+impl<T, F> ReconnectingMspClient<T, F>
+where
+    T: Read + Write,
+    F: FnMut() -> io::Result<MspClient<T>>,
+{
+    /// Create a new client. It does not connect until the first request.
+    pub fn new(connect: F, policy: ReconnectPolicy) -> ReconnectingMspClient<T, F> {
+        ReconnectingMspClient {
+            connect,
+            client: None,
+            policy,
+        }
+    }
+
+    /// Is there a live client from the last successful connect?
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Same as `MspClient::request`, connecting first if needed. A failed connect is reported
+    /// as `Io`, like a failed request.
+    pub fn request<P: Into<MspPacket>>(
+        &mut self,
+        packet: P,
+    ) -> Result<MspPacket, MspPacketParseError> {
+        let packet = packet.into();
+        match self.try_request(&packet) {
+            Err(MspPacketParseError::Io(kind)) if kind != io::ErrorKind::TimedOut => {
+                #[cfg(feature = "tracing")]
+                ::tracing::debug!(error = ?kind, "msp link failed");
+                match self.policy {
+                    ReconnectPolicy::Retry => self.try_request(&packet),
+                    ReconnectPolicy::Fail => Err(MspPacketParseError::Io(kind)),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Send `packet` on the current client, connecting first if there is none. The client is
+    /// dropped on any I/O error other than a timeout.
+    fn try_request(&mut self, packet: &MspPacket) -> Result<MspPacket, MspPacketParseError> {
+        if self.client.is_none() {
+            self.client = Some((self.connect)()?);
+        }
+
+        let result = self.client.as_mut().unwrap().request(packet.clone());
+        if let Err(MspPacketParseError::Io(kind)) = result {
+            if kind != io::ErrorKind::TimedOut {
+                self.client = None;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+struct MockTransport {
+    input: io::Cursor<Vec<u8>>,
+    written: Vec<u8>,
+    /// When set, every read waits this long and returns a single byte
+    drip: Option<Duration>,
+}
+
+// The mock transports advance a simulated clock in place of sleeping, so the timeouts do not
+// depend on how loaded the machine is. Until the first `sleep`, the clock follows real time.
+#[cfg(test)]
+struct SimulatedClock;
+
+#[cfg(test)]
+thread_local! {
+    static CLOCK: ::std::cell::Cell<Option<Instant>> = const { ::std::cell::Cell::new(None) };
+}
+
+#[cfg(test)]
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        CLOCK.with(|clock| clock.get()).unwrap_or_else(Instant::now)
+    }
+}
+
+#[cfg(test)]
+fn now() -> Instant {
+    SimulatedClock.now()
+}
+
+/// Advance the simulated clock by `d`
+#[cfg(test)]
+fn sleep(d: Duration) {
+    let t = now() + d;
+    CLOCK.with(|clock| clock.set(Some(t)));
+}
+
+/// How long a mock read waits for data before it times out
+#[cfg(test)]
+const MOCK_READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+#[cfg(test)]
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = match self.drip {
+            Some(delay) => {
+                sleep(delay);
+                buf.len().min(1)
+            }
+            None => buf.len(),
+        };
+        match self.input.read(&mut buf[..len])? {
+            0 => {
+                sleep(MOCK_READ_TIMEOUT);
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            n => Ok(n),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_client_request() {
+    let unrelated = MspPacket {
+        cmd: 105,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0xff; 4],
+    };
+    let response = MspPacket {
+        cmd: 108,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x01, 0x00, 0x02, 0x00, 0x03, 0x00],
+    };
+
+    let mut input = vec![0x00, b'$', 0x13];
+    for p in &[&unrelated, &response] {
+        let mut output = vec![0; p.packet_size_bytes_v2()];
+        p.serialize_v2(&mut output).unwrap();
+        input.extend_from_slice(&output);
+    }
+
+    let transport = MockTransport {
+        input: io::Cursor::new(input),
+        written: vec![],
+        drip: None,
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V2,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    );
+
+    let request = MspPacket {
+        cmd: 108,
+        direction: MspPacketDirection::ToFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![],
+    };
+    assert_eq!(Ok(response), client.request(request.clone()));
+
+    // the link is silent now, so the read timeout gives up long before the response timeout
+    let start = now();
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        client.request(request.clone())
+    );
+    assert!(now() - start < Duration::from_millis(500));
+
+    let mut expected = vec![0; request.packet_size_bytes_v2()];
+    request.serialize_v2(&mut expected).unwrap();
+    assert_eq!(
+        expected.repeat(2),
+        client.into_inner().written,
+        "both requests written as V2 frames"
+    );
+}
+
+#[test]
+fn test_client_slow_link() {
+    let response = MspPacket {
+        cmd: 108,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V1,
+        flag: 0,
+        data: vec![0x01, 0x00, 0x02, 0x00, 0x03, 0x00],
+    };
+    let mut input = vec![0; response.packet_size_bytes()];
+    response.serialize(&mut input).unwrap();
+    let request = MspPacket::request(108);
+
+    // 12 bytes a few milliseconds apart take longer than the read timeout in total
+    let transport = |input: &Vec<u8>| MockTransport {
+        input: io::Cursor::new(input.clone()),
+        written: vec![],
+        drip: Some(Duration::from_millis(5)),
+    };
+    let mut client = MspClient::new(
+        transport(&input),
+        MspVersion::V1,
+        Duration::from_millis(40),
+        Duration::from_secs(1),
+    );
+    assert_eq!(Ok(response), client.request(request.clone()));
+
+    let mut client = MspClient::new(
+        transport(&input),
+        MspVersion::V1,
+        Duration::from_millis(40),
+        Duration::from_millis(30),
+    );
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        client.request(request)
+    );
+}
+
+#[test]
+fn test_client_request_with_timeout() {
+    /// A flight controller that stays silent for a while before it answers
+    struct Slow {
+        silent: Duration,
+        inner: MockTransport,
+    }
+
+    impl Read for Slow {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.silent > Duration::from_millis(0) {
+                sleep(MOCK_READ_TIMEOUT);
+                self.silent -= MOCK_READ_TIMEOUT;
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for Slow {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let response = MspPacket::reply_to(&MspPacket::request(250), vec![]);
+    let mut input = vec![];
+    response.serialize_into(&mut input).unwrap();
+
+    let client = || {
+        let transport = Slow {
+            silent: Duration::from_millis(50),
+            inner: MockTransport {
+                input: io::Cursor::new(input.clone()),
+                written: vec![],
+                drip: None,
+            },
+        };
+        MspClient::new(
+            transport,
+            MspVersion::V1,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        )
+    };
+
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        client().request(250u16)
+    );
+    assert_eq!(
+        Ok(response),
+        client().request_with_timeout(250u16, Duration::from_millis(100))
+    );
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        client().request_with_timeout(250u16, Duration::from_millis(20))
+    );
+}
+
+#[test]
+fn test_client_request_with_retries() {
+    /// A flight controller that answers every request, but whose first `dropped` responses are
+    /// lost on the link
+    struct Lossy {
+        dropped: usize,
+        requests: usize,
+        response: Vec<u8>,
+        pending: io::Cursor<Vec<u8>>,
+        broken: bool,
+    }
+
+    impl Read for Lossy {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.broken {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            match self.pending.read(buf)? {
+                0 => {
+                    sleep(MOCK_READ_TIMEOUT);
+                    Err(io::ErrorKind::TimedOut.into())
+                }
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for Lossy {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.requests += 1;
+            if self.dropped > 0 {
+                self.dropped -= 1;
+            } else {
+                self.pending = io::Cursor::new(self.response.clone());
+            }
+            Ok(())
+        }
+    }
+
+    let response = MspPacket::reply_to(&MspPacket::request(101), vec![0x01]);
+    let mut frame = vec![];
+    response.serialize_into(&mut frame).unwrap();
+    let client = |dropped, broken| {
+        let transport = Lossy {
+            dropped,
+            requests: 0,
+            response: frame.clone(),
+            pending: io::Cursor::new(vec![]),
+            broken,
+        };
+        MspClient::new(
+            transport,
+            MspVersion::V1,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        )
+    };
+
+    let mut fc = client(2, false);
+    assert_eq!(Ok(response), fc.request_with_retries(101u16, 2));
+    assert_eq!(3, fc.into_inner().requests);
+
+    let mut fc = client(2, false);
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        fc.request_with_retries(101u16, 1)
+    );
+    assert_eq!(2, fc.into_inner().requests);
+
+    // a broken link is not retried
+    let mut fc = client(0, true);
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::BrokenPipe)),
+        fc.request_with_retries(101u16, 3)
+    );
+    assert_eq!(1, fc.into_inner().requests);
+}
+
+#[cfg(all(test, feature = "tracing"))]
+struct EventRecorder(::std::sync::Mutex<Vec<String>>);
+
+#[cfg(all(test, feature = "tracing"))]
+impl ::tracing::Subscriber for EventRecorder {
+    fn enabled(&self, _: &::tracing::Metadata) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &::tracing::span::Attributes) -> ::tracing::span::Id {
+        ::tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &::tracing::span::Id, _: &::tracing::span::Record) {}
+
+    fn record_follows_from(&self, _: &::tracing::span::Id, _: &::tracing::span::Id) {}
+
+    fn event(&self, event: &::tracing::Event) {
+        struct Message<'a>(&'a mut String);
+        impl<'a> ::tracing::field::Visit for Message<'a> {
+            fn record_debug(&mut self, field: &::tracing::field::Field, value: &dyn Debug) {
+                if field.name() == "message" {
+                    *self.0 = format!("{:?}", value);
+                }
+            }
+        }
+
+        let mut message = String::new();
+        event.record(&mut Message(&mut message));
+        self.0.lock().unwrap().push(message);
+    }
+
+    fn enter(&self, _: &::tracing::span::Id) {}
+
+    fn exit(&self, _: &::tracing::span::Id) {}
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_events() {
+    let response = MspPacket::reply_to(&MspPacket::request(101), vec![0x01]);
+    let mut input = vec![0; response.packet_size_bytes()];
+    response.serialize(&mut input).unwrap();
+    // a corrupt frame ahead of the response
+    input.extend_from_slice(b"$M?");
+    input.rotate_right(3);
+
+    let recorder = ::std::sync::Arc::new(EventRecorder(Default::default()));
+    ::tracing::subscriber::with_default(recorder.clone(), || {
+        let transport = MockTransport {
+            input: io::Cursor::new(input),
+            written: vec![],
+            drip: None,
+        };
+        let mut client = MspClient::new(
+            transport,
+            MspVersion::V1,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        );
+        client.request(MspPacket::request(101)).unwrap();
+    });
+
+    assert_eq!(
+        vec![
+            "msp request sent",
+            "msp parse error",
+            "msp packet parsed",
+            "msp response matched",
+        ],
+        *recorder.0.lock().unwrap()
+    );
+}
+
+#[test]
+fn test_client_request_typed() {
+    use messages::MspApiVersion;
+
+    let request = MspPacket::request(MspApiVersion::COMMAND);
+    let api = MspApiVersion {
+        protocol: 0,
+        major: 1,
+        minor: 44,
+    };
+    let response = MspPacket::reply_to(&request, api.encode().unwrap());
+    let mut unsupported = MspPacket::reply_to(&request, vec![]);
+    unsupported.direction = MspPacketDirection::Unsupported;
+
+    let client = |reply: &MspPacket| {
+        let mut input = vec![0; reply.packet_size_bytes()];
+        reply.serialize(&mut input).unwrap();
+        let transport = MockTransport {
+            input: io::Cursor::new(input),
+            written: vec![],
+            drip: None,
+        };
+        MspClient::new(
+            transport,
+            MspVersion::V1,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        )
+    };
+
+    let mut fc = client(&response);
+    assert_eq!(Ok(api), fc.request_typed());
+    let mut expected = vec![0; request.packet_size_bytes()];
+    request.serialize(&mut expected).unwrap();
+    assert_eq!(expected, fc.into_inner().written);
+
+    assert_eq!(
+        Err(MspPacketParseError::UnsupportedCommand {
+            cmd: MspApiVersion::COMMAND
+        }),
+        client(&unsupported).request_typed::<MspApiVersion>()
+    );
+}
+
+#[test]
+fn test_client_request_typed_with() {
+    use payload::PayloadReader;
+
+    /// `MSP2_COMMON_SETTING` request, naming a setting
+    struct SettingName(&'static str);
+
+    impl MspPayload for SettingName {
+        const COMMAND: u16 = 0x1003;
+
+        fn decode(_: &[u8]) -> Result<Self, MspPacketParseError> {
+            unimplemented!()
+        }
+
+        fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+            let mut data = self.0.as_bytes().to_vec();
+            data.push(0);
+            Ok(data)
+        }
+    }
+
+    /// `MSP2_COMMON_SETTING` response, the value of a `u16` setting
+    #[derive(Debug, PartialEq)]
+    struct SettingValue(u16);
+
+    impl MspPayload for SettingValue {
+        const COMMAND: u16 = 0x1003;
+
+        fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+            PayloadReader::new(data).read_u16().map(SettingValue)
+        }
+
+        fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+            Ok(self.0.to_le_bytes().to_vec())
+        }
+    }
+
+    let request = MspPacket::request(SettingName::COMMAND).with_payload(b"looptime\0".to_vec());
+    let response = MspPacket::reply_to(&request, vec![0xf4, 0x01]);
+    let transport = MockTransport {
+        input: io::Cursor::new(Vec::from(response)),
+        written: vec![],
+        drip: None,
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V2,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    );
+
+    assert_eq!(
+        Ok(SettingValue(500)),
+        client.request_typed_with(&SettingName("looptime"))
+    );
+    assert_eq!(Vec::from(request), client.into_inner().written);
+}
+
+#[test]
+fn test_client_unsupported() {
+    let request = MspPacket::request(0x1f01);
+    let mut unsupported = MspPacket::reply_to(&request, vec![]);
+    unsupported.direction = MspPacketDirection::Unsupported;
+
+    let transport = MockTransport {
+        input: io::Cursor::new(Vec::from(unsupported)),
+        written: vec![],
+        drip: None,
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V2,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    );
+    assert_eq!(
+        Err(MspPacketParseError::UnsupportedCommand { cmd: 0x1f01 }),
+        client.request(request)
+    );
+}
+
+#[test]
+fn test_client_request_into() {
+    let response = MspPacket::reply_to(&MspPacket::request(101), vec![0x01]);
+    let mut input = vec![0; response.packet_size_bytes()];
+    response.serialize(&mut input).unwrap();
+
+    let transport = MockTransport {
+        input: io::Cursor::new(input),
+        written: vec![],
+        drip: None,
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V1,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    );
+    assert_eq!(Ok(response), client.request(101u16));
+    assert_eq!(
+        vec![b'$', b'M', b'<', 0, 101, 101],
+        client.into_inner().written
+    );
+
+    assert_eq!(
+        MspPacket::request(108).with_payload(vec![0x01]),
+        MspPacket::from((108, vec![0x01]))
+    );
+}
+
+#[test]
+fn test_client_v1_tunnel() {
+    let request = MspPacket::request(0x1f01);
+    let response = MspPacket::reply_to(&request, vec![0x01, 0x02]);
+    let mut input = vec![];
+    response
+        .tunnel_v1()
+        .unwrap()
+        .serialize_into(&mut input)
+        .unwrap();
+
+    let transport = MockTransport {
+        input: io::Cursor::new(input),
+        written: vec![],
+        drip: None,
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V1,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    );
+    assert_eq!(Ok(response), client.request(request.clone()));
+
+    let written = client.into_inner().written;
+    assert_eq!(b"$M<", &written[..3]);
+    assert_eq!(255, written[4]);
+    assert_eq!(Ok(request), MspPacket::parse_one(&written));
+}
+
+#[test]
+fn test_client_idle_reset() {
+    /// Hands out one chunk per read, waiting before each
+    struct Chunks(Vec<(Duration, Vec<u8>)>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                sleep(MOCK_READ_TIMEOUT);
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            let (delay, chunk) = self.0.remove(0);
+            sleep(delay);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    impl Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let response = MspPacket::reply_to(&MspPacket::request(101), vec![0x01, 0x02]);
+    let mut frame = vec![0; response.packet_size_bytes()];
+    response.serialize(&mut frame).unwrap();
+    // half a frame, a pause, then the whole frame
+    let chunks = || {
+        Chunks(vec![
+            (Duration::from_millis(0), frame[..4].to_vec()),
+            (Duration::from_millis(30), frame.clone()),
+        ])
+    };
+    let client = |transport| {
+        MspClient::new(
+            transport,
+            MspVersion::V1,
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+        )
+    };
+
+    let mut fc = client(chunks()).with_idle_reset(Duration::from_millis(10));
+    assert_eq!(Ok(response), fc.request(101u16));
+
+    // without the idle reset the whole frame is read as the stale frame's payload
+    let mut fc = client(chunks());
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        fc.request(101u16)
+    );
+}
+
+#[test]
+fn test_client_observer() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Counter {
+        requests: AtomicUsize,
+        responses: AtomicUsize,
+        timeouts: AtomicUsize,
+        parse_errors: AtomicUsize,
+    }
+
+    impl MspObserver for Counter {
+        fn on_request(&self, _: &MspPacket) {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_response(&self, _: &MspPacket) {
+            self.responses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_timeout(&self, _: &MspPacket) {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_parse_error(&self, _: &MspPacketParseError) {
+            self.parse_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let unrelated = MspPacket::reply_to(&MspPacket::request(105), vec![0x01]);
+    let response = MspPacket::reply_to(&MspPacket::request(101), vec![0x01]);
+    // a corrupt frame and an unrelated packet ahead of the response
+    let mut input = b"$M?".to_vec();
+    let mut frame = vec![];
+    for p in &[&unrelated, &response] {
+        p.serialize_into(&mut frame).unwrap();
+        input.extend_from_slice(&frame);
+    }
+
+    let counter = Arc::new(Counter::default());
+    let transport = MockTransport {
+        input: io::Cursor::new(input),
+        written: vec![],
+        drip: None,
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V1,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    )
+    .with_observer(counter.clone());
+
+    assert_eq!(Ok(response), client.request(101u16));
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        client.request(101u16)
+    );
+
+    assert_eq!(2, counter.requests.load(Ordering::Relaxed));
+    assert_eq!(1, counter.responses.load(Ordering::Relaxed));
+    assert_eq!(1, counter.timeouts.load(Ordering::Relaxed));
+    assert_eq!(1, counter.parse_errors.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_reconnecting_client() {
+    /// A transport whose link is gone when `broken` is set
+    struct Flaky {
+        inner: MockTransport,
+        broken: bool,
+    }
+
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.broken {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let response = MspPacket::reply_to(&MspPacket::request(101), vec![0x01]);
+    let mut input = vec![];
+    response.serialize_into(&mut input).unwrap();
+
+    // the first connection breaks, every later one answers
+    let connector = |connects: &mut usize| {
+        *connects += 1;
+        let transport = Flaky {
+            inner: MockTransport {
+                input: io::Cursor::new(input.clone()),
+                written: vec![],
+                drip: None,
+            },
+            broken: *connects == 1,
+        };
+        Ok(MspClient::new(
+            transport,
+            MspVersion::V1,
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        ))
+    };
+
+    let mut connects = 0;
+    let mut client =
+        ReconnectingMspClient::new(|| connector(&mut connects), ReconnectPolicy::Retry);
+    assert!(!client.is_connected());
+    assert_eq!(Ok(response.clone()), client.request(101));
+    assert!(client.is_connected());
+    drop(client);
+    assert_eq!(2, connects);
+
+    let mut connects = 0;
+    let mut client = ReconnectingMspClient::new(|| connector(&mut connects), ReconnectPolicy::Fail);
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::BrokenPipe)),
+        client.request(101)
+    );
+    assert!(!client.is_connected());
+    assert_eq!(Ok(response), client.request(101));
+    drop(client);
+    assert_eq!(2, connects);
+
+    // a silent flight controller is not a broken link
+    let mut connects = 0;
+    let mut client = ReconnectingMspClient::new(
+        || {
+            connects += 1;
+            Ok(MspClient::new(
+                MockTransport {
+                    input: io::Cursor::new(vec![]),
+                    written: vec![],
+                    drip: None,
+                },
+                MspVersion::V1,
+                Duration::from_millis(10),
+                Duration::from_secs(1),
+            ))
+        },
+        ReconnectPolicy::Retry,
+    );
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::TimedOut)),
+        client.request(101)
+    );
+    assert!(client.is_connected());
+    drop(client);
+    assert_eq!(1, connects);
+}
+
+#[test]
+fn test_client_request_sequence_v1() {
+    let request = MspPacket::request(108);
+    // tunneled responses are parsed as the V2 packets they carry
+    let first = MspPacket {
+        version: MspVersion::V2,
+        ..MspPacket::reply_to(&request.clone().with_sequence(1), vec![0x01])
+    };
+    let second = MspPacket {
+        version: MspVersion::V2,
+        ..MspPacket::reply_to(&request.clone().with_sequence(2), vec![0x02])
+    };
+
+    // the reply to the first request arrives twice
+    let mut input = vec![];
+    let mut frame = vec![];
+    for p in &[&first, &first, &second] {
+        p.tunnel_v1().unwrap().serialize_into(&mut frame).unwrap();
+        input.extend_from_slice(&frame);
+    }
+
+    let transport = MockTransport {
+        input: io::Cursor::new(input),
+        written: vec![],
+        drip: Some(Duration::from_millis(0)),
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V1,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    );
+    assert_eq!(Ok(first), client.request(request.clone().with_sequence(1)));
+    assert_eq!(Ok(second), client.request(request.clone().with_sequence(2)));
+
+    let written = client.into_inner().written;
+    let mut parser = MspParser::new();
+    let sent: Vec<_> = written
+        .iter()
+        .filter_map(|b| parser.parse(*b).unwrap())
+        .map(|p| p.flag)
+        .collect();
+    assert_eq!(vec![1, 2], sent, "the sequence is sent in tunneled V2 frames");
+}
+
+#[test]
+fn test_client_request_sequence() {
+    let stale = MspPacket {
+        cmd: 108,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x01],
+    }
+    .with_sequence(1);
+    let response = stale.clone().with_payload(vec![0x02]).with_sequence(2);
+
+    let mut input = vec![];
+    for p in &[&stale, &response] {
+        let mut output = vec![0; p.packet_size_bytes_v2()];
+        p.serialize_v2(&mut output).unwrap();
+        input.extend_from_slice(&output);
+    }
+
+    let transport = MockTransport {
+        input: io::Cursor::new(input),
+        written: vec![],
+        drip: None,
+    };
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V2,
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+    );
+
+    let request = MspPacket::request(108).with_sequence(2);
+    assert_eq!(Ok(response), client.request(request));
+}
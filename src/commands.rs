@@ -1,187 +1,412 @@
-#[derive(PrimitiveEnum, Debug, Copy, Clone, PartialEq)]
-#[allow(non_camel_case_types)]
+// `MspCommandCode` and `MspCommand` are both generated from the one table below, so their codes
+// and feature gates cannot drift apart. The aliases after the `;` are firmware specific names for
+// a code of the table; `MspCommand` decodes the code as the alias when its feature is enabled.
+macro_rules! msp_commands {
+    (
+        $($(#[$cfg:meta])* $code_name:ident = $code:tt => $variant:ident,)*
+        ;
+        $($(#[$alias_cfg:meta])* $alias_name:ident = $target:ident => $alias_variant:ident,)*
+    ) => {
+        /// MSP command values, used for command encapsulation
+        #[derive(PrimitiveEnum, Debug, Copy, Clone, PartialEq)]
+        #[allow(non_camel_case_types)]
+        pub enum MspCommandCode {
+            $($(#[$cfg])* $code_name = $code,)*
+        }
 
-/// MSP command values, used for command encapsulation
-pub enum MspCommandCode {
-    MSP_API_VERSION = 1,
-    MSP_FC_VARIANT = 2,
-    MSP_FC_VERSION = 3,
-    MSP_BOARD_INFO = 4,
-    MSP_BUILD_INFO = 5,
+        impl MspCommandCode {
+            $(
+                $(#[$alias_cfg])*
+                pub const $alias_name: MspCommandCode = MspCommandCode::$target;
+            )*
+        }
+
+        /// Known MSP commands, for readable logging and dispatch. Codes without a named variant
+        /// convert to `Unknown`, so no command code is lost.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        #[non_exhaustive]
+        pub enum MspCommand {
+            $($(#[$cfg])* $variant,)*
+            $($(#[$alias_cfg])* $alias_variant,)*
+            Unknown(u16),
+        }
+
+        impl MspCommand {
+            /// Protocol name of the command, such as `"MSP_STATUS"`
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $($(#[$cfg])* MspCommand::$variant => stringify!($code_name),)*
+                    $($(#[$alias_cfg])* MspCommand::$alias_variant => stringify!($alias_name),)*
+                    MspCommand::Unknown(_) => "UNKNOWN",
+                }
+            }
+        }
+
+        impl From<u16> for MspCommand {
+            fn from(code: u16) -> Self {
+                match code {
+                    $(
+                        $(#[$alias_cfg])*
+                        c if c == MspCommandCode::$target as u16 => MspCommand::$alias_variant,
+                    )*
+                    $($(#[$cfg])* $code => MspCommand::$variant,)*
+                    c => MspCommand::Unknown(c),
+                }
+            }
+        }
+
+        impl From<MspCommand> for u16 {
+            fn from(command: MspCommand) -> Self {
+                match command {
+                    $($(#[$cfg])* MspCommand::$variant => $code,)*
+                    $(
+                        $(#[$alias_cfg])*
+                        MspCommand::$alias_variant => MspCommandCode::$target as u16,
+                    )*
+                    MspCommand::Unknown(c) => c,
+                }
+            }
+        }
+    };
+}
+
+msp_commands! {
+    MSP_API_VERSION = 1 => ApiVersion,
+    MSP_FC_VARIANT = 2 => FcVariant,
+    MSP_FC_VERSION = 3 => FcVersion,
+    MSP_BOARD_INFO = 4 => BoardInfo,
+    MSP_BUILD_INFO = 5 => BuildInfo,
+
+    #[cfg(any(feature = "betaflight", feature = "inav"))]
+    MSP_NAME = 10 => Name,
+    #[cfg(any(feature = "betaflight", feature = "inav"))]
+    MSP_SET_NAME = 11 => SetName,
 
     // MSP commands for Cleanflight original features
-    MSP_BATTERY_CONFIG = 32,
-    MSP_SET_BATTERY_CONFIG = 33,
-    MSP_MODE_RANGES = 34,
-    MSP_SET_MODE_RANGE = 35,
-    MSP_FEATURE = 36,
-    MSP_SET_FEATURE = 37,
-    MSP_BOARD_ALIGNMENT = 38,
-    MSP_SET_BOARD_ALIGNMENT = 39,
-    MSP_AMPERAGE_METER_CONFIG = 40,
-    MSP_SET_AMPERAGE_METER_CONFIG = 41,
-    MSP_MIXER = 42,
-    MSP_SET_MIXER = 43,
-    MSP_RX_CONFIG = 44,
-    MSP_SET_RX_CONFIG = 45,
-    MSP_LED_COLORS = 46,
-    MSP_SET_LED_COLORS = 47,
-    MSP_LED_STRIP_CONFIG = 48,
-    MSP_SET_LED_STRIP_CONFIG = 49,
-    MSP_RSSI_CONFIG = 50,
-    MSP_SET_RSSI_CONFIG = 51,
-    MSP_ADJUSTMENT_RANGES = 52,
-    MSP_SET_ADJUSTMENT_RANGE = 53,
-    MSP_CF_SERIAL_CONFIG = 54,
-    MSP_SET_CF_SERIAL_CONFIG = 55,
-    MSP_VOLTAGE_METER_CONFIG = 56,
-    MSP_SET_VOLTAGE_METER_CONFIG = 57,
-    MSP_SONAR = 58,
-    MSP_PID_CONTROLLER = 59,
-    MSP_SET_PID_CONTROLLER = 60,
-    MSP_ARMING_CONFIG = 61,
-    MSP_SET_ARMING_CONFIG = 62,
-    MSP_DATAFLASH_SUMMARY = 70,
-    MSP_DATAFLASH_READ = 71,
-    MSP_DATAFLASH_ERASE = 72,
-    MSP_LOOP_TIME = 73,
-    MSP_SET_LOOP_TIME = 74,
-    MSP_FAILSAFE_CONFIG = 75,
-    MSP_SET_FAILSAFE_CONFIG = 76,
-    MSP_RXFAIL_CONFIG = 77,
-    MSP_SET_RXFAIL_CONFIG = 78,
-    MSP_SDCARD_SUMMARY = 79,
-    MSP_BLACKBOX_CONFIG = 80,
-    MSP_SET_BLACKBOX_CONFIG = 81,
-    MSP_TRANSPONDER_CONFIG = 82,
-    MSP_SET_TRANSPONDER_CONFIG = 83,
-
-    MSP_OSD_CONFIG = 84,     //out message         Get osd settings - baseflight
-    MSP_SET_OSD_CONFIG = 85, //in message          Set osd settings - baseflight
-
-    MSP_OSD_CHAR_READ = 86, //out message         Get osd settings - betaflight
-    MSP_OSD_CHAR_WRITE = 87, //in message          Set osd settings - betaflight
-
-    MSP_LED_STRIP_MODECOLOR = 127,
-    MSP_SET_LED_STRIP_MODECOLOR = 221,
-
-    MSP_VOLTAGE_METERS = 128,
-    MSP_AMPERAGE_METERS = 129,
-    MSP_BATTERY_STATE = 130,
-
-    MSP_MOTOR_CONFIG = 131,
+    #[cfg(feature = "cleanflight")]
+    MSP_BATTERY_CONFIG = 32 => BatteryConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_BATTERY_CONFIG = 33 => SetBatteryConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_MODE_RANGES = 34 => ModeRanges,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_MODE_RANGE = 35 => SetModeRange,
+    #[cfg(feature = "cleanflight")]
+    MSP_FEATURE = 36 => Feature,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_FEATURE = 37 => SetFeature,
+    #[cfg(feature = "cleanflight")]
+    MSP_BOARD_ALIGNMENT = 38 => BoardAlignment,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_BOARD_ALIGNMENT = 39 => SetBoardAlignment,
+    #[cfg(feature = "cleanflight")]
+    MSP_AMPERAGE_METER_CONFIG = 40 => AmperageMeterConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_AMPERAGE_METER_CONFIG = 41 => SetAmperageMeterConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_MIXER = 42 => Mixer,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_MIXER = 43 => SetMixer,
+    #[cfg(feature = "cleanflight")]
+    MSP_RX_CONFIG = 44 => RxConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_RX_CONFIG = 45 => SetRxConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_LED_COLORS = 46 => LedColors,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_LED_COLORS = 47 => SetLedColors,
+    #[cfg(feature = "cleanflight")]
+    MSP_LED_STRIP_CONFIG = 48 => LedStripConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_LED_STRIP_CONFIG = 49 => SetLedStripConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_RSSI_CONFIG = 50 => RssiConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_RSSI_CONFIG = 51 => SetRssiConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_ADJUSTMENT_RANGES = 52 => AdjustmentRanges,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_ADJUSTMENT_RANGE = 53 => SetAdjustmentRange,
+    #[cfg(feature = "cleanflight")]
+    MSP_CF_SERIAL_CONFIG = 54 => CfSerialConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_CF_SERIAL_CONFIG = 55 => SetCfSerialConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_VOLTAGE_METER_CONFIG = 56 => VoltageMeterConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_VOLTAGE_METER_CONFIG = 57 => SetVoltageMeterConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SONAR = 58 => Sonar,
+    #[cfg(feature = "cleanflight")]
+    MSP_PID_CONTROLLER = 59 => PidController,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_PID_CONTROLLER = 60 => SetPidController,
+    #[cfg(feature = "cleanflight")]
+    MSP_ARMING_CONFIG = 61 => ArmingConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_ARMING_CONFIG = 62 => SetArmingConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_DATAFLASH_SUMMARY = 70 => DataflashSummary,
+    #[cfg(feature = "cleanflight")]
+    MSP_DATAFLASH_READ = 71 => DataflashRead,
+    #[cfg(feature = "cleanflight")]
+    MSP_DATAFLASH_ERASE = 72 => DataflashErase,
+    #[cfg(feature = "cleanflight")]
+    MSP_LOOP_TIME = 73 => LoopTime,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_LOOP_TIME = 74 => SetLoopTime,
+    #[cfg(feature = "cleanflight")]
+    MSP_FAILSAFE_CONFIG = 75 => FailsafeConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_FAILSAFE_CONFIG = 76 => SetFailsafeConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_RXFAIL_CONFIG = 77 => RxfailConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_RXFAIL_CONFIG = 78 => SetRxfailConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SDCARD_SUMMARY = 79 => SdcardSummary,
+    #[cfg(feature = "cleanflight")]
+    MSP_BLACKBOX_CONFIG = 80 => BlackboxConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_BLACKBOX_CONFIG = 81 => SetBlackboxConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_TRANSPONDER_CONFIG = 82 => TransponderConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_TRANSPONDER_CONFIG = 83 => SetTransponderConfig,
+
+    #[cfg(feature = "cleanflight")]
+    MSP_OSD_CONFIG = 84 => OsdConfig, //out message         Get osd settings - baseflight
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_OSD_CONFIG = 85 => SetOsdConfig, //in message          Set osd settings - baseflight
+
+    #[cfg(feature = "cleanflight")]
+    MSP_OSD_CHAR_READ = 86 => OsdCharRead, //out message         Get osd settings - betaflight
+    #[cfg(feature = "cleanflight")]
+    MSP_OSD_CHAR_WRITE = 87 => OsdCharWrite, //in message          Set osd settings - betaflight
+
+    #[cfg(feature = "betaflight")]
+    MSP_VTX_CONFIG = 88 => VtxConfig, //out message         Get vtx settings - betaflight
+    #[cfg(feature = "betaflight")]
+    MSP_SET_VTX_CONFIG = 89 => SetVtxConfig, //in message          Set vtx settings - betaflight
+
+    #[cfg(feature = "cleanflight")]
+    MSP_LED_STRIP_MODECOLOR = 127 => LedStripModecolor,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_LED_STRIP_MODECOLOR = 221 => SetLedStripModecolor,
+
+    #[cfg(feature = "cleanflight")]
+    MSP_VOLTAGE_METERS = 128 => VoltageMeters,
+    #[cfg(feature = "cleanflight")]
+    MSP_AMPERAGE_METERS = 129 => AmperageMeters,
+    #[cfg(feature = "cleanflight")]
+    MSP_BATTERY_STATE = 130 => BatteryState,
+
+    #[cfg(feature = "cleanflight")]
+    MSP_MOTOR_CONFIG = 131 => MotorConfig,
 
     // OSD commands
-    MSP_OSD_VIDEO_CONFIG = 180,
-    MSP_SET_OSD_VIDEO_CONFIG = 181,
-    MSP_OSD_VIDEO_STATUS = 182,
-    MSP_OSD_ELEMENT_SUMMARY = 183,
-    MSP_OSD_LAYOUT_CONFIG = 184,
-    MSP_SET_OSD_LAYOUT_CONFIG = 185,
+    #[cfg(feature = "cleanflight")]
+    MSP_OSD_VIDEO_CONFIG = 180 => OsdVideoConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_OSD_VIDEO_CONFIG = 181 => SetOsdVideoConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_OSD_VIDEO_STATUS = 182 => OsdVideoStatus,
+    #[cfg(feature = "cleanflight")]
+    MSP_OSD_ELEMENT_SUMMARY = 183 => OsdElementSummary,
+    #[cfg(feature = "cleanflight")]
+    MSP_OSD_LAYOUT_CONFIG = 184 => OsdLayoutConfig,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_OSD_LAYOUT_CONFIG = 185 => SetOsdLayoutConfig,
 
     // Multiwii MSP commands
-    MSP_IDENT = 100,
-    MSP_STATUS = 101,
-    MSP_RAW_IMU = 102,
-    MSP_SERVO = 103,
-    MSP_MOTOR = 104,
-    MSP_RC = 105,
-    MSP_RAW_GPS = 106,
-    MSP_COMP_GPS = 107,
-    MSP_ATTITUDE = 108,
-    MSP_ALTITUDE = 109,
-    MSP_ANALOG = 110,
-    MSP_RC_TUNING = 111,
-    MSP_PID = 112,
-    MSP_BOX = 113,
-    MSP_MISC = 114,
-    MSP_MOTOR_PINS = 115,
-    MSP_BOXNAMES = 116,
-    MSP_PIDNAMES = 117,
-    MSP_WP = 118,
-    MSP_BOXIDS = 119,
-    MSP_SERVO_CONFIGURATIONS = 120,
-    MSP_MOTOR_3D_CONFIG = 124,
-    MSP_RC_DEADBAND = 125,
-    MSP_SENSOR_ALIGNMENT = 126,
-
-    MSP_SET_RAW_RC = 200,
-    MSP_SET_RAW_GPS = 201,
-    MSP_SET_PID = 202,
-    MSP_SET_BOX = 203,
-    MSP_SET_RC_TUNING = 204,
-    MSP_ACC_CALIBRATION = 205,
-    MSP_MAG_CALIBRATION = 206,
-    MSP_SET_MISC = 207,
-    MSP_RESET_CONF = 208,
-    MSP_SET_WP = 209,
-    MSP_SELECT_SETTING = 210,
-    MSP_SET_HEAD = 211,
-    MSP_SET_SERVO_CONFIGURATION = 212,
-    MSP_SET_MOTOR = 214,
-    MSP_SET_3D = 217,
-    MSP_SET_RC_DEADBAND = 218,
-    MSP_SET_RESET_CURR_PID = 219,
-    MSP_SET_SENSOR_ALIGNMENT = 220,
-
-    // MSP_BIND =               240,
-    MSP_SERVO_MIX_RULES = 241,
-    MSP_SET_SERVO_MIX_RULE = 242,
-
-    MSP_EEPROM_WRITE = 250,
-
-    MSP_DEBUGMSG = 253,
-    MSP_DEBUG = 254,
-
-    MSP_BF_CONFIG = 66,
+    MSP_IDENT = 100 => Ident,
+    MSP_STATUS = 101 => Status,
+    MSP_RAW_IMU = 102 => RawImu,
+    MSP_SERVO = 103 => Servo,
+    MSP_MOTOR = 104 => Motor,
+    MSP_RC = 105 => Rc,
+    MSP_RAW_GPS = 106 => RawGps,
+    MSP_COMP_GPS = 107 => CompGps,
+    MSP_ATTITUDE = 108 => Attitude,
+    MSP_ALTITUDE = 109 => Altitude,
+    MSP_ANALOG = 110 => Analog,
+    MSP_RC_TUNING = 111 => RcTuning,
+    MSP_PID = 112 => Pid,
+    MSP_BOX = 113 => Box,
+    MSP_MISC = 114 => Misc,
+    MSP_MOTOR_PINS = 115 => MotorPins,
+    MSP_BOXNAMES = 116 => Boxnames,
+    MSP_PIDNAMES = 117 => Pidnames,
+    MSP_WP = 118 => Wp,
+    MSP_BOXIDS = 119 => Boxids,
+    MSP_SERVO_CONFIGURATIONS = 120 => ServoConfigurations,
+    #[cfg(feature = "inav")]
+    MSP_NAV_STATUS = 121 => NavStatus,
+    MSP_MOTOR_3D_CONFIG = 124 => Motor3dConfig,
+    MSP_RC_DEADBAND = 125 => RcDeadband,
+    MSP_SENSOR_ALIGNMENT = 126 => SensorAlignment,
+
+    MSP_SET_RAW_RC = 200 => SetRawRc,
+    MSP_SET_RAW_GPS = 201 => SetRawGps,
+    MSP_SET_PID = 202 => SetPid,
+    MSP_SET_BOX = 203 => SetBox,
+    MSP_SET_RC_TUNING = 204 => SetRcTuning,
+    MSP_ACC_CALIBRATION = 205 => AccCalibration,
+    MSP_MAG_CALIBRATION = 206 => MagCalibration,
+    MSP_SET_MISC = 207 => SetMisc,
+    MSP_RESET_CONF = 208 => ResetConf,
+    MSP_SET_WP = 209 => SetWp,
+    MSP_SELECT_SETTING = 210 => SelectSetting,
+    MSP_SET_HEAD = 211 => SetHead,
+    MSP_SET_SERVO_CONFIGURATION = 212 => SetServoConfiguration,
+    MSP_SET_MOTOR = 214 => SetMotor,
+    MSP_SET_3D = 217 => Set3d,
+    MSP_SET_RC_DEADBAND = 218 => SetRcDeadband,
+    MSP_SET_RESET_CURR_PID = 219 => SetResetCurrPid,
+    MSP_SET_SENSOR_ALIGNMENT = 220 => SetSensorAlignment,
+
+    // MSP_BIND = 240,
+    MSP_SERVO_MIX_RULES = 241 => ServoMixRules,
+    MSP_SET_SERVO_MIX_RULE = 242 => SetServoMixRule,
+
+    MSP_EEPROM_WRITE = 250 => EepromWrite,
+
+    MSP_DEBUGMSG = 253 => Debugmsg,
+    MSP_DEBUG = 254 => Debug,
+
+    #[cfg(feature = "cleanflight")]
+    MSP_BF_CONFIG = 66 => BfConfig,
 
     // Additional baseflight commands that are not compatible with MultiWii
-    MSP_UID = 160,          // Unique device ID
-    MSP_STATUS_EX = 150,    // cycletime, errors_count, CPU load, sensor present etc
-    MSP_ACC_TRIM = 240,     // get acc angle trim values
-    MSP_SET_ACC_TRIM = 239, // set acc angle trim values
-    MSP_GPS_SV_INFO = 164,  // get Signal Strength
+    #[cfg(feature = "cleanflight")]
+    MSP_UID = 160 => Uid, // Unique device ID
+    #[cfg(feature = "cleanflight")]
+    MSP_STATUS_EX = 150 => StatusEx, // cycletime, errors_count, CPU load, sensor present etc
+    #[cfg(feature = "cleanflight")]
+    MSP_ACC_TRIM = 240 => AccTrim, // get acc angle trim values
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_ACC_TRIM = 239 => SetAccTrim, // set acc angle trim values
+    #[cfg(feature = "cleanflight")]
+    MSP_GPS_SV_INFO = 164 => GpsSvInfo, // get Signal Strength
 
     // Additional private MSP for baseflight configurator
-    MSP_RX_MAP = 64,     // get channel map (also returns number of channels total)
-    MSP_SET_RX_MAP = 65, // set rc map, numchannels to set comes from MSP_RX_MAP
+    #[cfg(feature = "cleanflight")]
+    MSP_RX_MAP = 64 => RxMap, // get channel map (also returns number of channels total)
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_RX_MAP = 65 => SetRxMap, // set rc map, numchannels to set comes from MSP_RX_MAP
 
-    MSP_SET_REBOOT = 68,    // reboot settings
-    MSP_BF_BUILD_INFO = 69, // build date as well as some space for future expansion,
+    #[cfg(feature = "cleanflight")]
+    MSP_SET_REBOOT = 68 => SetReboot, // reboot settings
+    #[cfg(feature = "cleanflight")]
+    MSP_BF_BUILD_INFO = 69 => BfBuildInfo, // build date as well as some space for future expansion,
 
     // Betaflight
-    MSP_ADVANCED_CONFIG = 90,
-    MSP_SET_ADVANCED_CONFIG = 91,
+    #[cfg(feature = "betaflight")]
+    MSP_ADVANCED_CONFIG = 90 => AdvancedConfig,
+    #[cfg(feature = "betaflight")]
+    MSP_SET_ADVANCED_CONFIG = 91 => SetAdvancedConfig,
+
+    #[cfg(feature = "betaflight")]
+    MSP_FILTER_CONFIG = 92 => FilterConfig,
+    #[cfg(feature = "betaflight")]
+    MSP_SET_FILTER_CONFIG = 93 => SetFilterConfig,
 
-    MSP_FILTER_CONFIG = 92,
-    MSP_SET_FILTER_CONFIG = 93,
+    #[cfg(feature = "betaflight")]
+    MSP_PID_ADVANCED = 94 => PidAdvanced,
+    #[cfg(feature = "betaflight")]
+    MSP_SET_PID_ADVANCED = 95 => SetPidAdvanced,
 
-    MSP_PID_ADVANCED = 94,
-    MSP_SET_PID_ADVANCED = 95,
+    #[cfg(feature = "betaflight")]
+    MSP_SENSOR_CONFIG = 96 => SensorConfig,
+    #[cfg(feature = "betaflight")]
+    MSP_SET_SENSOR_CONFIG = 97 => SetSensorConfig,
 
-    MSP_SENSOR_CONFIG = 96,
-    MSP_SET_SENSOR_CONFIG = 97,
+    #[cfg(feature = "betaflight")]
+    MSP_MOTOR_TELEMETRY = 139 => MotorTelemetry, // per motor ESC telemetry
 
     // Inav
-    MSP2_COMMON_SETTING = 0x1003, //in/out message    Returns the value for a setting
-    MSP2_COMMON_SET_SETTING = 0x1004, //in message        Sets the value for a setting
+    #[cfg(feature = "inav")]
+    MSP2_COMMON_SETTING = 0x1003 => CommonSetting, //in/out message    Returns the value for a setting
+    #[cfg(feature = "inav")]
+    MSP2_COMMON_SET_SETTING = 0x1004 => CommonSetSetting, //in message        Sets the value for a setting
+
+    #[cfg(feature = "inav")]
+    MSP2_MOTOR_MIXER = 0x1005 => MotorMixer,
+    #[cfg(feature = "inav")]
+    MSP2_SET_MOTOR_MIXER = 0x1006 => SetMotorMixer,
+
+    #[cfg(feature = "inav")]
+    MSP2_COMMON_SETTING_INFO = 0x1007 => CommonSettingInfo,
+    #[cfg(feature = "inav")]
+    MSP2_COMMON_PG_LIST = 0x1008 => CommonPgList,
+
+    #[cfg(feature = "inav")]
+    MSP2_SERIAL_CONFIG = 0x1009 => SerialConfig,
+    #[cfg(feature = "inav")]
+    MSP2_SET_SERIAL_CONFIG = 0x100A => SetSerialConfig,
+
+    // sent unsolicited by external sensors
+    #[cfg(feature = "inav")]
+    MSP2_SENSOR_RANGEFINDER = 0x1F01 => SensorRangefinder,
+    #[cfg(feature = "inav")]
+    MSP2_SENSOR_GPS = 0x1F03 => SensorGps,
+
+    #[cfg(feature = "inav")]
+    MSP2_INAV_OSD_LAYOUTS = 0x2012 => InavOsdLayouts,
+    #[cfg(feature = "inav")]
+    MSP2_INAV_OSD_SET_LAYOUT_ITEM = 0x2013 => InavOsdSetLayoutItem,
+    #[cfg(feature = "inav")]
+    MSP2_INAV_OSD_ALARMS = 0x2014 => InavOsdAlarms,
+    #[cfg(feature = "inav")]
+    MSP2_INAV_OSD_SET_ALARMS = 0x2015 => InavOsdSetAlarms,
+    #[cfg(feature = "inav")]
+    MSP2_INAV_OSD_PREFERENCES = 0x2016 => InavOsdPreferences,
+    #[cfg(feature = "inav")]
+    MSP2_INAV_OSD_SET_PREFERENCES = 0x2017 => InavOsdSetPreferences,
+
+    #[cfg(feature = "inav")]
+    MSP2_INAV_SERVO_MIXER = 0x2020 => InavServoMixer,
+    #[cfg(feature = "inav")]
+    MSP2_INAV_SET_SERVO_MIXER = 0x2021 => InavSetServoMixer,
+    ;
+    // Betaflight reuses the code of `MSP_OSD_VIDEO_STATUS`
+    #[cfg(feature = "betaflight")]
+    MSP_DISPLAYPORT = MSP_OSD_VIDEO_STATUS => DisplayPort,
+}
 
-    MSP2_MOTOR_MIXER = 0x1005,
-    MSP2_SET_MOTOR_MIXER = 0x1006,
+#[test]
+fn test_command() {
+    assert_eq!(MspCommand::Status, MspCommand::from(101));
+    assert_eq!("MSP_STATUS", MspCommand::Status.name());
+    assert_eq!(101, u16::from(MspCommand::Status));
+    assert_eq!(MspCommandCode::MSP_STATUS as u16, u16::from(MspCommand::Status));
 
-    MSP2_COMMON_SETTING_INFO = 0x1007,
-    MSP2_COMMON_PG_LIST = 0x1008,
+    #[cfg(feature = "inav")]
+    assert_eq!(
+        MspCommand::from(MspCommandCode::MSP_NAV_STATUS as u16).name(),
+        "MSP_NAV_STATUS"
+    );
+    #[cfg(not(feature = "inav"))]
+    assert_eq!(MspCommand::Unknown(121), MspCommand::from(121));
 
-    MSP2_SERIAL_CONFIG = 0x1009,
-    MSP2_SET_SERIAL_CONFIG = 0x100A,
+    #[cfg(feature = "inav")]
+    {
+        assert_eq!(MspCommand::SetSerialConfig, MspCommand::from(0x100a));
+        assert_eq!("MSP2_SET_SERIAL_CONFIG", MspCommand::SetSerialConfig.name());
+    }
+    #[cfg(not(feature = "inav"))]
+    assert_eq!(MspCommand::Unknown(0x100a), MspCommand::from(0x100a));
 
-    MSP2_INAV_OSD_LAYOUTS = 0x2012,
-    MSP2_INAV_OSD_SET_LAYOUT_ITEM = 0x2013,
-    MSP2_INAV_OSD_ALARMS = 0x2014,
-    MSP2_INAV_OSD_SET_ALARMS = 0x2015,
-    MSP2_INAV_OSD_PREFERENCES = 0x2016,
-    MSP2_INAV_OSD_SET_PREFERENCES = 0x2017,
+    #[cfg(feature = "betaflight")]
+    assert_eq!("MSP_DISPLAYPORT", MspCommand::from(182).name());
+    #[cfg(feature = "betaflight")]
+    assert_eq!(182, u16::from(MspCommand::DisplayPort));
+    #[cfg(all(feature = "cleanflight", not(feature = "betaflight")))]
+    assert_eq!("MSP_OSD_VIDEO_STATUS", MspCommand::from(182).name());
+    #[cfg(feature = "cleanflight")]
+    assert_eq!(182, MspCommandCode::MSP_OSD_VIDEO_STATUS as u16);
 
-    MSP2_INAV_SERVO_MIXER = 0x2020,
-    MSP2_INAV_SET_SERVO_MIXER = 0x2021,
+    assert_eq!(MspCommand::Unknown(99), MspCommand::from(99));
+    assert_eq!("UNKNOWN", MspCommand::Unknown(99).name());
+    assert_eq!(99, u16::from(MspCommand::Unknown(99)));
 }
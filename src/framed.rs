@@ -0,0 +1,160 @@
+//! Parser for MSP frames carried with a length prefix, as done by some TCP bridges
+
+use prelude::v1::*;
+
+use packet::{MspPacket, MspPacketParseError};
+
+/// Default cap on the length of a prefixed frame
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 1024;
+
+/// Smallest possible frame, a V1 frame without payload
+const MIN_FRAME_LENGTH: usize = 6;
+
+/// Parser for a stream where every MSP frame is preceded by its length, as a little-endian
+/// `u16`. Each complete frame is decoded as a single packet, so a corrupt frame only drops that
+/// frame. A prefix outside `6..=max_frame_length` fails with `InvalidDataLength`; the frame
+/// boundaries are lost at that point and the caller should reconnect or resynchronize.
+#[derive(Debug, Clone)]
+pub struct FramedMspParser {
+    prefix: [u8; 2],
+    prefix_received: usize,
+    frame: Vec<u8>,
+    frame_length: usize,
+    max_frame_length: usize,
+}
+
+impl FramedMspParser {
+    /// Create a new parser accepting frames up to `DEFAULT_MAX_FRAME_LENGTH` bytes
+    pub fn new() -> FramedMspParser {
+        FramedMspParser {
+            prefix: [0; 2],
+            prefix_received: 0,
+            frame: Vec::new(),
+            frame_length: 0,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Reject length prefixes above `max_frame_length`
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> FramedMspParser {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Are we waiting for the length prefix of a brand new frame?
+    pub fn state_is_between_packets(&self) -> bool {
+        self.prefix_received == 0
+    }
+
+    /// Parse the next input byte. Returns a packet once the frame announced by the last length
+    /// prefix has been fully received.
+    pub fn parse(&mut self, input: u8) -> Result<Option<MspPacket>, MspPacketParseError> {
+        if self.prefix_received < self.prefix.len() {
+            self.prefix[self.prefix_received] = input;
+            self.prefix_received += 1;
+
+            if self.prefix_received == self.prefix.len() {
+                let length = u16::from_le_bytes(self.prefix) as usize;
+                if length < MIN_FRAME_LENGTH || length > self.max_frame_length {
+                    self.reset();
+                    return Err(MspPacketParseError::InvalidDataLength);
+                }
+                self.frame_length = length;
+                self.frame.clear();
+                self.frame.reserve(length);
+            }
+            return Ok(None);
+        }
+
+        self.frame.push(input);
+        if self.frame.len() < self.frame_length {
+            return Ok(None);
+        }
+
+        let packet = MspPacket::parse_one(&self.frame);
+        self.reset();
+        packet.map(Some)
+    }
+
+    /// Parse a chunk of input bytes, calling `on_packet` for every packet completed by it. Stops
+    /// at the first parse error, like `MspParser::feed`.
+    pub fn feed<F: FnMut(MspPacket)>(
+        &mut self,
+        input: &[u8],
+        mut on_packet: F,
+    ) -> Result<(), MspPacketParseError> {
+        for b in input {
+            if let Some(packet) = self.parse(*b)? {
+                on_packet(packet);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drop a partially received length prefix and the buffered frame
+    pub fn reset(&mut self) {
+        self.prefix_received = 0;
+        self.frame_length = 0;
+        self.frame.clear();
+    }
+}
+
+impl Default for FramedMspParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+use packet::{MspPacketDirection, MspVersion};
+
+#[test]
+fn test_framed_roundtrip() {
+    let packets = [
+        MspPacket {
+            cmd: 108,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V1,
+            flag: 0,
+            data: vec![0x01, 0x00, 0x02, 0x00, 0x03, 0x00],
+        },
+        MspPacket {
+            cmd: 0x1003,
+            direction: MspPacketDirection::ToFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: vec![],
+        },
+    ];
+
+    let mut input = vec![];
+    for packet in &packets {
+        let mut frame = match packet.version {
+            MspVersion::V1 => vec![0; packet.packet_size_bytes()],
+            MspVersion::V2 => vec![0; packet.packet_size_bytes_v2()],
+        };
+        match packet.version {
+            MspVersion::V1 => packet.serialize(&mut frame).unwrap(),
+            MspVersion::V2 => packet.serialize_v2(&mut frame).unwrap(),
+        }
+        input.extend_from_slice(&(frame.len() as u16).to_le_bytes());
+        input.extend_from_slice(&frame);
+    }
+
+    let mut parser = FramedMspParser::new();
+    let mut parsed = vec![];
+    parser.feed(&input, |p| parsed.push(p)).unwrap();
+    assert_eq!(packets.to_vec(), parsed);
+    assert!(parser.state_is_between_packets());
+
+    let mut parser = FramedMspParser::new().with_max_frame_length(8);
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        parser.feed(&input, |_| {})
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        parser.feed(&[0x02, 0x00], |_| {})
+    );
+}
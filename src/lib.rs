@@ -3,7 +3,9 @@
 //! Incomplete. Includes some structures from Cleanflight and Betaflight.
 
 #![cfg_attr(not(feature = "std"), no_std)]
-#![cfg_attr(not(feature = "std"), feature(alloc))]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("the `std` or the `alloc` feature must be enabled");
 
 #[cfg(not(feature = "std"))]
 #[macro_use]
@@ -11,8 +13,6 @@ extern crate alloc;
 
 extern crate packed_struct;
 
-extern crate crc_any;
-
 #[macro_use]
 extern crate packed_struct_codegen;
 
@@ -21,11 +21,56 @@ extern crate serde_derive;
 
 extern crate serde;
 
+#[cfg(feature = "futures")]
+extern crate futures;
+
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(any(test, feature = "quickcheck"))]
+extern crate quickcheck;
+
+#[cfg(feature = "serialport")]
+extern crate serialport;
+
+#[cfg(feature = "bytes")]
+extern crate bytes;
+
+#[cfg(test)]
+extern crate serde_json;
+
 mod prelude;
 
+#[cfg(all(test, feature = "std"))]
+mod alloc_counter;
+
+#[cfg(any(test, feature = "quickcheck"))]
+mod arbitrary;
+
+#[cfg(feature = "std")]
+mod client;
 mod commands;
+mod framed;
+pub mod messages;
 mod packet;
+mod payload;
+#[cfg(feature = "std")]
+mod relay;
+#[cfg(feature = "serialport")]
+mod serial;
+#[cfg(feature = "futures")]
+mod stream;
 pub mod structs;
 
+#[cfg(feature = "std")]
+pub use client::*;
 pub use commands::*;
+pub use framed::*;
 pub use packet::*;
+pub use payload::*;
+#[cfg(feature = "std")]
+pub use relay::*;
+#[cfg(feature = "serialport")]
+pub use serial::*;
+#[cfg(feature = "futures")]
+pub use stream::*;
@@ -0,0 +1,2443 @@
+//! Typed MSP messages, decoded from and encoded into packet payloads
+
+// `usize::is_multiple_of` needs Rust 1.87, keep the `%` length checks
+#![allow(clippy::manual_is_multiple_of)]
+
+use prelude::v1::*;
+
+use commands::MspCommandCode;
+use packet::{MspPacket, MspPacketParseError};
+use payload::{MspBorrowedPayload, MspPayload, PayloadReader, PayloadWriter};
+
+/// `MSP_API_VERSION` response
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspApiVersion {
+    pub protocol: u8,
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl MspPayload for MspApiVersion {
+    const COMMAND: u16 = MspCommandCode::MSP_API_VERSION as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspApiVersion {
+            protocol: r.read_u8()?,
+            major: r.read_u8()?,
+            minor: r.read_u8()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(vec![self.protocol, self.major, self.minor])
+    }
+}
+
+/// `MSP_FC_VARIANT` response, a four character firmware identifier such as `"BTFL"` or `"INAV"`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspFcVariant(pub String);
+
+impl MspPayload for MspFcVariant {
+    const COMMAND: u16 = MspCommandCode::MSP_FC_VARIANT as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if data.len() != 4 || !data.is_ascii() {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        Ok(MspFcVariant(data.iter().map(|b| *b as char).collect()))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        if self.0.len() != 4 || !self.0.is_ascii() {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        Ok(self.0.as_bytes().to_vec())
+    }
+}
+
+/// `MSP_FC_VERSION` response
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspFcVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl MspPayload for MspFcVersion {
+    const COMMAND: u16 = MspCommandCode::MSP_FC_VERSION as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspFcVersion {
+            major: r.read_u8()?,
+            minor: r.read_u8()?,
+            patch: r.read_u8()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(vec![self.major, self.minor, self.patch])
+    }
+}
+
+fn decode_utf8(data: &[u8]) -> Result<String, MspPacketParseError> {
+    String::from_utf8(data.to_vec()).map_err(|_| MspPacketParseError::InvalidData)
+}
+
+fn decode_utf8_borrowed(data: &[u8]) -> Result<&str, MspPacketParseError> {
+    from_utf8(data).map_err(|_| MspPacketParseError::InvalidData)
+}
+
+/// `MSP_BOARD_INFO` response. Firmware older than the board type field sends only the identifier
+/// and hardware revision, so the later fields are optional.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspBoardInfo {
+    /// Four character board identifier, such as `"S405"`
+    pub board_id: String,
+    pub hardware_revision: u16,
+    /// OSD support of the board
+    pub board_type: Option<u8>,
+    /// Target capability flags
+    pub target_capabilities: Option<u8>,
+    /// Firmware target name, such as `"STM32F405"`
+    pub target_name: Option<String>,
+}
+
+/// `MspBoardInfo` borrowing the strings from the payload
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspBoardInfoRef<'a> {
+    pub board_id: &'a str,
+    pub hardware_revision: u16,
+    pub board_type: Option<u8>,
+    pub target_capabilities: Option<u8>,
+    pub target_name: Option<&'a str>,
+}
+
+impl<'a> From<MspBoardInfoRef<'a>> for MspBoardInfo {
+    fn from(info: MspBoardInfoRef<'a>) -> Self {
+        MspBoardInfo {
+            board_id: info.board_id.to_owned(),
+            hardware_revision: info.hardware_revision,
+            board_type: info.board_type,
+            target_capabilities: info.target_capabilities,
+            target_name: info.target_name.map(|n| n.to_owned()),
+        }
+    }
+}
+
+impl MspBorrowedPayload for MspBoardInfo {
+    type Borrowed<'a> = MspBoardInfoRef<'a>;
+
+    fn decode_borrowed(data: &[u8]) -> Result<MspBoardInfoRef<'_>, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        let board_id = decode_utf8_borrowed(r.read_bytes(4)?)?;
+        let hardware_revision = r.read_u16()?;
+        let board_type = r.read_u8().ok();
+        let target_capabilities = r.read_u8().ok();
+        let target_name = match r.read_u8() {
+            Ok(len) => Some(decode_utf8_borrowed(r.read_bytes(len as usize)?)?),
+            Err(_) => None,
+        };
+
+        Ok(MspBoardInfoRef {
+            board_id,
+            hardware_revision,
+            board_type,
+            target_capabilities,
+            target_name,
+        })
+    }
+}
+
+impl MspPayload for MspBoardInfo {
+    const COMMAND: u16 = MspCommandCode::MSP_BOARD_INFO as u16;
+
+    /// Reads the fields present in the payload; any fields after the target name are ignored
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        MspBoardInfo::decode_borrowed(data).map(MspBoardInfo::from)
+    }
+
+    /// Writes the optional fields in order, up to the first one that is missing
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        if self.board_id.len() != 4 {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let mut w = PayloadWriter::with_capacity(9);
+        w.write_bytes(self.board_id.as_bytes())
+            .write_u16(self.hardware_revision);
+        if let Some(board_type) = self.board_type {
+            w.write_u8(board_type);
+            if let Some(capabilities) = self.target_capabilities {
+                w.write_u8(capabilities);
+                if let Some(ref name) = self.target_name {
+                    if name.len() > 0xff {
+                        return Err(MspPacketParseError::InvalidDataLength);
+                    }
+                    w.write_u8(name.len() as u8).write_bytes(name.as_bytes());
+                }
+            }
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// Length of the build date, such as `"Nov 14 2023"`
+pub const BUILD_DATE_LENGTH: usize = 11;
+/// Length of the build time, such as `"10:25:31"`
+pub const BUILD_TIME_LENGTH: usize = 8;
+/// Length of the short git revision
+pub const GIT_SHORT_REVISION_LENGTH: usize = 7;
+
+/// `MSP_BUILD_INFO` response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspBuildInfo {
+    pub date: String,
+    pub time: String,
+    pub git_hash: String,
+}
+
+impl MspPayload for MspBuildInfo {
+    const COMMAND: u16 = MspCommandCode::MSP_BUILD_INFO as u16;
+
+    /// Any bytes after the git revision, such as the build options of newer firmware, are ignored
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        let date = r.read_bytes(BUILD_DATE_LENGTH)?;
+        let time = r.read_bytes(BUILD_TIME_LENGTH)?;
+        let git_hash = r.read_bytes(GIT_SHORT_REVISION_LENGTH)?;
+
+        Ok(MspBuildInfo {
+            date: decode_utf8(date)?,
+            time: decode_utf8(time)?,
+            git_hash: decode_utf8(git_hash)?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        if self.date.len() != BUILD_DATE_LENGTH
+            || self.time.len() != BUILD_TIME_LENGTH
+            || self.git_hash.len() != GIT_SHORT_REVISION_LENGTH
+        {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        let mut w = PayloadWriter::with_capacity(
+            BUILD_DATE_LENGTH + BUILD_TIME_LENGTH + GIT_SHORT_REVISION_LENGTH,
+        );
+        w.write_bytes(self.date.as_bytes())
+            .write_bytes(self.time.as_bytes())
+            .write_bytes(self.git_hash.as_bytes());
+        Ok(w.into_vec())
+    }
+}
+
+/// Longest craft name accepted by the firmware
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+pub const MAX_CRAFT_NAME_LENGTH: usize = 16;
+
+/// `MSP_NAME` response, the ASCII craft name. It is set with `MspSetName`.
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspCraftName(pub String);
+
+/// `MspCraftName` borrowing the name from the payload
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspCraftNameRef<'a>(pub &'a str);
+
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+impl MspBorrowedPayload for MspCraftName {
+    type Borrowed<'a> = MspCraftNameRef<'a>;
+
+    /// Trailing null bytes are trimmed
+    fn decode_borrowed(data: &[u8]) -> Result<MspCraftNameRef<'_>, MspPacketParseError> {
+        if !data.is_ascii() {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let len = data.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        Ok(MspCraftNameRef(decode_utf8_borrowed(&data[..len])?))
+    }
+}
+
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+impl MspPayload for MspCraftName {
+    const COMMAND: u16 = MspCommandCode::MSP_NAME as u16;
+
+    /// Trailing null bytes are trimmed
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        MspCraftName::decode_borrowed(data).map(|name| MspCraftName(name.0.to_owned()))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        if !self.0.is_ascii() {
+            return Err(MspPacketParseError::InvalidData);
+        }
+        if self.0.len() > MAX_CRAFT_NAME_LENGTH {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        Ok(self.0.as_bytes().to_vec())
+    }
+}
+
+/// `MSP_SET_NAME` request, in the same layout as `MspCraftName`
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspSetName(pub String);
+
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+impl MspPayload for MspSetName {
+    const COMMAND: u16 = MspCommandCode::MSP_SET_NAME as u16;
+
+    /// Trailing null bytes are trimmed
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        MspCraftName::decode(data).map(|name| MspSetName(name.0))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        MspCraftName(self.0.clone()).encode()
+    }
+}
+
+/// `MSP_SERVO` response, one output value per servo
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspServo(pub Vec<u16>);
+
+impl MspPayload for MspServo {
+    const COMMAND: u16 = MspCommandCode::MSP_SERVO as u16;
+
+    /// The servo count is taken from the payload length, which must be even
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if data.len() % 2 != 0 {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let mut r = PayloadReader::new(data);
+        let mut servos = Vec::with_capacity(data.len() / 2);
+        while r.remaining() > 0 {
+            servos.push(r.read_u16()?);
+        }
+        Ok(MspServo(servos))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(self.0.iter().flat_map(|s| s.to_le_bytes()).collect())
+    }
+}
+
+/// Configuration of a single servo, as carried in `MSP_SERVO_CONFIGURATIONS`
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspServoConfRecord {
+    pub min: u16,
+    pub max: u16,
+    pub middle: u16,
+    /// percent, -125 to 125
+    pub rate: i8,
+    /// Deprecated, 255 when unused
+    pub forward_from_channel: u8,
+    /// Deprecated, input reversing is done at mixer level
+    pub reversed_sources: u32,
+}
+
+/// Size of a `MspServoConfRecord` on the wire
+pub const SERVO_CONF_RECORD_SIZE: usize = 14;
+
+/// `MSP_SERVO_CONFIGURATIONS` response, one 14 byte record per servo: `min`, `max` and `middle`
+/// as u16, `rate` as i8, two unused bytes, `forward_from_channel` as u8 and
+/// `reversed_sources` as u32, all little-endian
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspServoConf(pub Vec<MspServoConfRecord>);
+
+impl MspPayload for MspServoConf {
+    const COMMAND: u16 = MspCommandCode::MSP_SERVO_CONFIGURATIONS as u16;
+
+    /// The servo count is taken from the payload length, which must be a whole number of records
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if data.len() % SERVO_CONF_RECORD_SIZE != 0 {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        let mut r = PayloadReader::new(data);
+        let mut servos = Vec::with_capacity(data.len() / SERVO_CONF_RECORD_SIZE);
+        while r.remaining() > 0 {
+            let min = r.read_u16()?;
+            let max = r.read_u16()?;
+            let middle = r.read_u16()?;
+            let rate = r.read_i8()?;
+            r.read_bytes(2)?;
+            servos.push(MspServoConfRecord {
+                min,
+                max,
+                middle,
+                rate,
+                forward_from_channel: r.read_u8()?,
+                reversed_sources: r.read_u32()?,
+            });
+        }
+        Ok(MspServoConf(servos))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(self.0.len() * SERVO_CONF_RECORD_SIZE);
+        for servo in &self.0 {
+            w.write_u16(servo.min)
+                .write_u16(servo.max)
+                .write_u16(servo.middle)
+                .write_i8(servo.rate)
+                .write_bytes(&[0, 0])
+                .write_u8(servo.forward_from_channel)
+                .write_u32(servo.reversed_sources);
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_COMP_GPS` response, the course back to the home position
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspCompGps {
+    /// meters
+    pub distance_to_home_m: u16,
+    /// degrees, -180 to 180
+    pub direction_to_home_deg: i16,
+    /// Toggled by the firmware on every GPS update
+    pub gps_update: u8,
+}
+
+impl MspPayload for MspCompGps {
+    const COMMAND: u16 = MspCommandCode::MSP_COMP_GPS as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspCompGps {
+            distance_to_home_m: r.read_u16()?,
+            direction_to_home_deg: r.read_i16()?,
+            gps_update: r.read_u8()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(5);
+        w.write_u16(self.distance_to_home_m)
+            .write_i16(self.direction_to_home_deg)
+            .write_u8(self.gps_update);
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_ALTITUDE` response
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspAltitude {
+    /// centimeters
+    pub estimated_alt_cm: i32,
+    /// variometer, cm/s
+    pub vario_cm_s: i16,
+}
+
+impl MspPayload for MspAltitude {
+    const COMMAND: u16 = MspCommandCode::MSP_ALTITUDE as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspAltitude {
+            estimated_alt_cm: r.read_i32()?,
+            vario_cm_s: r.read_i16()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(6);
+        w.write_i32(self.estimated_alt_cm)
+            .write_i16(self.vario_cm_s);
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_BATTERY_STATE` response
+#[cfg(feature = "cleanflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspBatteryState {
+    pub cell_count: u8,
+    /// mAh
+    pub capacity: u16,
+    /// 0.1V
+    pub voltage: u8,
+    /// mAh
+    pub mah_drawn: u16,
+    /// 0.01A
+    pub amperage: i16,
+    /// Battery state and alert flags
+    pub state: u8,
+    /// 0.01V, only sent by newer firmware
+    pub voltage_precise: Option<u16>,
+}
+
+#[cfg(feature = "cleanflight")]
+impl MspPayload for MspBatteryState {
+    const COMMAND: u16 = MspCommandCode::MSP_BATTERY_STATE as u16;
+
+    /// Reads the legacy 9 byte layout, plus the precise voltage when the payload is long enough
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspBatteryState {
+            cell_count: r.read_u8()?,
+            capacity: r.read_u16()?,
+            voltage: r.read_u8()?,
+            mah_drawn: r.read_u16()?,
+            amperage: r.read_i16()?,
+            state: r.read_u8()?,
+            voltage_precise: r.read_u16().ok(),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(11);
+        w.write_u8(self.cell_count)
+            .write_u16(self.capacity)
+            .write_u8(self.voltage)
+            .write_u16(self.mah_drawn)
+            .write_i16(self.amperage)
+            .write_u8(self.state);
+        if let Some(voltage) = self.voltage_precise {
+            w.write_u16(voltage);
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_MOTOR` response, one output value per motor. A value of zero means the motor is
+/// stopped. The motors are driven directly with `MspSetMotor`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspMotor(pub Vec<u16>);
+
+impl MspPayload for MspMotor {
+    const COMMAND: u16 = MspCommandCode::MSP_MOTOR as u16;
+
+    /// The motor count is taken from the payload length, which must be even
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if data.len() % 2 != 0 {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let mut r = PayloadReader::new(data);
+        let mut motors = Vec::with_capacity(data.len() / 2);
+        while r.remaining() > 0 {
+            motors.push(r.read_u16()?);
+        }
+        Ok(MspMotor(motors))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(self.0.iter().flat_map(|m| m.to_le_bytes()).collect())
+    }
+}
+
+/// `MSP_SET_MOTOR` request, in the same layout as `MspMotor`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspSetMotor(pub Vec<u16>);
+
+impl MspPayload for MspSetMotor {
+    const COMMAND: u16 = MspCommandCode::MSP_SET_MOTOR as u16;
+
+    /// The motor count is taken from the payload length, which must be even
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        MspMotor::decode(data).map(|motors| MspSetMotor(motors.0))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(self.0.iter().flat_map(|m| m.to_le_bytes()).collect())
+    }
+}
+
+/// `MSP_DEBUG` response, the firmware's debug registers. Betaflight sends four values, but the
+/// count depends on the firmware and its build, so all values in the payload are kept.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspDebug(pub Vec<i16>);
+
+impl MspPayload for MspDebug {
+    const COMMAND: u16 = MspCommandCode::MSP_DEBUG as u16;
+
+    /// The value count is taken from the payload length, which must be even
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if data.len() % 2 != 0 {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let mut r = PayloadReader::new(data);
+        let mut values = Vec::with_capacity(data.len() / 2);
+        while r.remaining() > 0 {
+            values.push(r.read_i16()?);
+        }
+        Ok(MspDebug(values))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(self.0.iter().flat_map(|v| v.to_le_bytes()).collect())
+    }
+}
+
+/// `MSP_UID` response, the 96 bit unique identifier of the flight controller's MCU
+#[cfg(feature = "cleanflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MspUid(pub [u32; 3]);
+
+#[cfg(feature = "cleanflight")]
+impl MspUid {
+    /// The identifier as 24 lowercase hex digits, one group of eight per word, as shown by
+    /// configurators
+    pub fn to_hex_string(&self) -> String {
+        format!("{:08x}{:08x}{:08x}", self.0[0], self.0[1], self.0[2])
+    }
+}
+
+#[cfg(feature = "cleanflight")]
+impl MspPayload for MspUid {
+    const COMMAND: u16 = MspCommandCode::MSP_UID as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if data.len() != 12 {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let mut r = PayloadReader::new(data);
+        Ok(MspUid([r.read_u32()?, r.read_u32()?, r.read_u32()?]))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(12);
+        for word in &self.0 {
+            w.write_u32(*word);
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// Orientation of a sensor relative to the board, as used in `MSP_SENSOR_ALIGNMENT`
+#[cfg(feature = "cleanflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MspSensorAlign {
+    /// The driver's own default orientation
+    Default,
+    Cw0,
+    Cw90,
+    Cw180,
+    Cw270,
+    Cw0Flip,
+    Cw90Flip,
+    Cw180Flip,
+    Cw270Flip,
+    Unknown(u8),
+}
+
+#[cfg(feature = "cleanflight")]
+const SENSOR_ALIGNS: [MspSensorAlign; 9] = [
+    MspSensorAlign::Default,
+    MspSensorAlign::Cw0,
+    MspSensorAlign::Cw90,
+    MspSensorAlign::Cw180,
+    MspSensorAlign::Cw270,
+    MspSensorAlign::Cw0Flip,
+    MspSensorAlign::Cw90Flip,
+    MspSensorAlign::Cw180Flip,
+    MspSensorAlign::Cw270Flip,
+];
+
+#[cfg(feature = "cleanflight")]
+impl From<u8> for MspSensorAlign {
+    fn from(value: u8) -> Self {
+        SENSOR_ALIGNS
+            .get(value as usize)
+            .cloned()
+            .unwrap_or(MspSensorAlign::Unknown(value))
+    }
+}
+
+#[cfg(feature = "cleanflight")]
+impl From<MspSensorAlign> for u8 {
+    fn from(align: MspSensorAlign) -> Self {
+        match align {
+            MspSensorAlign::Unknown(v) => v,
+            a => SENSOR_ALIGNS.iter().position(|s| *s == a).unwrap_or(0) as u8,
+        }
+    }
+}
+
+/// `MSP_SENSOR_ALIGNMENT` response. Later firmware appends the alignment of each gyro, which
+/// is ignored.
+#[cfg(feature = "cleanflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MspSensorAlignment {
+    pub gyro: MspSensorAlign,
+    pub acc: MspSensorAlign,
+    pub mag: MspSensorAlign,
+}
+
+#[cfg(feature = "cleanflight")]
+impl MspPayload for MspSensorAlignment {
+    const COMMAND: u16 = MspCommandCode::MSP_SENSOR_ALIGNMENT as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspSensorAlignment {
+            gyro: r.read_u8()?.into(),
+            acc: r.read_u8()?.into(),
+            mag: r.read_u8()?.into(),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(3);
+        w.write_u8(self.gyro.into())
+            .write_u8(self.acc.into())
+            .write_u8(self.mag.into());
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_SET_SENSOR_ALIGNMENT` request, in the same layout as `MspSensorAlignment`
+#[cfg(feature = "cleanflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MspSetSensorAlignment {
+    pub gyro: MspSensorAlign,
+    pub acc: MspSensorAlign,
+    pub mag: MspSensorAlign,
+}
+
+#[cfg(feature = "cleanflight")]
+impl From<MspSensorAlignment> for MspSetSensorAlignment {
+    fn from(a: MspSensorAlignment) -> Self {
+        MspSetSensorAlignment {
+            gyro: a.gyro,
+            acc: a.acc,
+            mag: a.mag,
+        }
+    }
+}
+
+#[cfg(feature = "cleanflight")]
+impl MspPayload for MspSetSensorAlignment {
+    const COMMAND: u16 = MspCommandCode::MSP_SET_SENSOR_ALIGNMENT as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        MspSensorAlignment::decode(data).map(MspSetSensorAlignment::from)
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        MspSensorAlignment {
+            gyro: self.gyro,
+            acc: self.acc,
+            mag: self.mag,
+        }
+        .encode()
+    }
+}
+
+/// `MSP_RC_TUNING` response, in the Cleanflight layout. Firmware has appended fields over time,
+/// so the later ones are only present when the payload is long enough:
+///
+/// * 10 bytes: rc rate and expo through the TPA breakpoint
+/// * 11 bytes: `yaw_expo`
+/// * 12 bytes: `yaw_rc_rate`
+/// * 14 bytes: `pitch_rc_rate` and `pitch_expo`
+/// * 16 bytes: `throttle_limit_type` and `throttle_limit_percent`
+/// * 22 bytes: `rate_limits`
+#[cfg(feature = "cleanflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspRcTuning {
+    pub rc_rate: u8,
+    pub rc_expo: u8,
+    pub rate_roll: u8,
+    pub rate_pitch: u8,
+    pub rate_yaw: u8,
+    pub dyn_thr_pid: u8,
+    pub thr_mid: u8,
+    pub thr_expo: u8,
+    pub tpa_breakpoint: u16,
+    pub yaw_expo: Option<u8>,
+    pub yaw_rc_rate: Option<u8>,
+    pub pitch_rc_rate: Option<u8>,
+    pub pitch_expo: Option<u8>,
+    pub throttle_limit_type: Option<u8>,
+    pub throttle_limit_percent: Option<u8>,
+    /// Roll, pitch and yaw rate limits in degrees per second
+    pub rate_limits: Option<[u16; 3]>,
+}
+
+#[cfg(feature = "cleanflight")]
+impl MspPayload for MspRcTuning {
+    const COMMAND: u16 = MspCommandCode::MSP_RC_TUNING as u16;
+
+    /// Any bytes after the rate limits are ignored
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        let rates = r.read_bytes(8)?;
+        let tpa_breakpoint = r.read_u16()?;
+
+        // each optional group is read whole, and only if the previous one was present
+        let yaw_expo = r.read_u8().ok();
+        let yaw_rc_rate = yaw_expo.and_then(|_| r.read_u8().ok());
+        let pitch = yaw_rc_rate.and_then(|_| r.read_bytes(2).ok());
+        let throttle_limit = pitch.and_then(|_| r.read_bytes(2).ok());
+        let rate_limits = match throttle_limit {
+            Some(_) if r.remaining() >= 6 => Some([r.read_u16()?, r.read_u16()?, r.read_u16()?]),
+            _ => None,
+        };
+
+        Ok(MspRcTuning {
+            rc_rate: rates[0],
+            rc_expo: rates[1],
+            rate_roll: rates[2],
+            rate_pitch: rates[3],
+            rate_yaw: rates[4],
+            dyn_thr_pid: rates[5],
+            thr_mid: rates[6],
+            thr_expo: rates[7],
+            tpa_breakpoint,
+            yaw_expo,
+            yaw_rc_rate,
+            pitch_rc_rate: pitch.map(|b| b[0]),
+            pitch_expo: pitch.map(|b| b[1]),
+            throttle_limit_type: throttle_limit.map(|b| b[0]),
+            throttle_limit_percent: throttle_limit.map(|b| b[1]),
+            rate_limits,
+        })
+    }
+
+    /// Writes the optional fields in order, up to the first group that is missing
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(22);
+        w.write_bytes(&[
+            self.rc_rate,
+            self.rc_expo,
+            self.rate_roll,
+            self.rate_pitch,
+            self.rate_yaw,
+            self.dyn_thr_pid,
+            self.thr_mid,
+            self.thr_expo,
+        ])
+        .write_u16(self.tpa_breakpoint);
+
+        if let Some(yaw_expo) = self.yaw_expo {
+            w.write_u8(yaw_expo);
+            if let Some(yaw_rc_rate) = self.yaw_rc_rate {
+                w.write_u8(yaw_rc_rate);
+                if let (Some(rate), Some(expo)) = (self.pitch_rc_rate, self.pitch_expo) {
+                    w.write_u8(rate).write_u8(expo);
+                    if let (Some(kind), Some(percent)) =
+                        (self.throttle_limit_type, self.throttle_limit_percent)
+                    {
+                        w.write_u8(kind).write_u8(percent);
+                        if let Some(limits) = self.rate_limits {
+                            for limit in &limits {
+                                w.write_u16(*limit);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// Convert a latitude or longitude in degrees to the 1e-7 degree units used on the wire.
+/// Fails with `InvalidData` if the value is not finite or beyond ±180 degrees.
+pub fn coordinate_from_degrees(degrees: f64) -> Result<i32, MspPacketParseError> {
+    if !degrees.is_finite() || degrees.abs() > 180.0 {
+        return Err(MspPacketParseError::InvalidData);
+    }
+
+    let scaled = degrees * 1e7;
+    Ok(if scaled < 0.0 {
+        scaled - 0.5
+    } else {
+        scaled + 0.5
+    } as i32)
+}
+
+/// `MSP_SET_RAW_GPS` request, injecting a GPS fix into the flight controller
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspSetRawGps {
+    pub fix_type: u8,
+    pub num_sat: u8,
+    /// 1e-7 degrees
+    pub lat: i32,
+    /// 1e-7 degrees
+    pub lon: i32,
+    /// meters
+    pub alt: u16,
+    /// cm/s
+    pub ground_speed: u16,
+}
+
+impl MspSetRawGps {
+    /// Build a fix from a position in degrees. Fails with `InvalidData` if the latitude is
+    /// beyond ±90 degrees or the longitude beyond ±180 degrees.
+    pub fn from_degrees(
+        fix_type: u8,
+        num_sat: u8,
+        lat: f64,
+        lon: f64,
+        alt: u16,
+        ground_speed: u16,
+    ) -> Result<MspSetRawGps, MspPacketParseError> {
+        if lat.abs() > 90.0 {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        Ok(MspSetRawGps {
+            fix_type,
+            num_sat,
+            lat: coordinate_from_degrees(lat)?,
+            lon: coordinate_from_degrees(lon)?,
+            alt,
+            ground_speed,
+        })
+    }
+}
+
+impl MspPayload for MspSetRawGps {
+    const COMMAND: u16 = MspCommandCode::MSP_SET_RAW_GPS as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspSetRawGps {
+            fix_type: r.read_u8()?,
+            num_sat: r.read_u8()?,
+            lat: r.read_i32()?,
+            lon: r.read_i32()?,
+            alt: r.read_u16()?,
+            ground_speed: r.read_u16()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(14);
+        w.write_u8(self.fix_type)
+            .write_u8(self.num_sat)
+            .write_i32(self.lat)
+            .write_i32(self.lon)
+            .write_u16(self.alt)
+            .write_u16(self.ground_speed);
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_EEPROM_WRITE` request, saving the configuration to flash. It has no payload, and the
+/// flight controller acknowledges it with an empty reply.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspEepromWrite;
+
+impl MspPayload for MspEepromWrite {
+    const COMMAND: u16 = MspCommandCode::MSP_EEPROM_WRITE as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if !data.is_empty() {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        Ok(MspEepromWrite)
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(vec![])
+    }
+}
+
+/// Same as `MspPacket::request(MspEepromWrite::COMMAND)`
+impl From<MspEepromWrite> for MspPacket {
+    fn from(_: MspEepromWrite) -> MspPacket {
+        MspPacket::request(MspEepromWrite::COMMAND)
+    }
+}
+
+/// `MSP_ACC_CALIBRATION` request, starting the accelerometer calibration. It has no payload,
+/// and the flight controller acknowledges it with an empty reply.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspAccCalibration;
+
+impl MspPayload for MspAccCalibration {
+    const COMMAND: u16 = MspCommandCode::MSP_ACC_CALIBRATION as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        if !data.is_empty() {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        Ok(MspAccCalibration)
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        Ok(vec![])
+    }
+}
+
+/// Same as `MspPacket::request(MspAccCalibration::COMMAND)`
+impl From<MspAccCalibration> for MspPacket {
+    fn from(_: MspAccCalibration) -> MspPacket {
+        MspPacket::request(MspAccCalibration::COMMAND)
+    }
+}
+
+/// Telemetry reported by the ESC of one motor
+#[cfg(feature = "betaflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspMotorTelemetryRecord {
+    /// RPM as reported by the firmware, which converts from eRPM with its configured motor pole
+    /// count
+    pub rpm: u32,
+    /// Share of invalid telemetry frames, 0.01%
+    pub invalid_percent: u16,
+    /// °C
+    pub temperature: u8,
+    /// 0.01V
+    pub voltage: u16,
+    /// 0.01A
+    pub current: u16,
+    /// mAh
+    pub consumption: u16,
+}
+
+/// Size of a `MspMotorTelemetryRecord` on the wire
+#[cfg(feature = "betaflight")]
+pub const MOTOR_TELEMETRY_RECORD_SIZE: usize = 13;
+
+/// `MSP_MOTOR_TELEMETRY` response, a motor count byte followed by one 13 byte record per motor:
+/// `rpm` as u32, `invalid_percent` as u16, `temperature` as u8, then `voltage`, `current` and
+/// `consumption` as u16, all little-endian
+#[cfg(feature = "betaflight")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspMotorTelemetry(pub Vec<MspMotorTelemetryRecord>);
+
+#[cfg(feature = "betaflight")]
+impl MspPayload for MspMotorTelemetry {
+    const COMMAND: u16 = MspCommandCode::MSP_MOTOR_TELEMETRY as u16;
+
+    /// Reads as many records as announced by the count byte; any bytes after them are ignored
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        let count = r.read_u8()? as usize;
+        if r.remaining() < count * MOTOR_TELEMETRY_RECORD_SIZE {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let mut motors = Vec::with_capacity(count);
+        for _ in 0..count {
+            motors.push(MspMotorTelemetryRecord {
+                rpm: r.read_u32()?,
+                invalid_percent: r.read_u16()?,
+                temperature: r.read_u8()?,
+                voltage: r.read_u16()?,
+                current: r.read_u16()?,
+                consumption: r.read_u16()?,
+            });
+        }
+        Ok(MspMotorTelemetry(motors))
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        if self.0.len() > 0xff {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        let mut w = PayloadWriter::with_capacity(1 + self.0.len() * MOTOR_TELEMETRY_RECORD_SIZE);
+        w.write_u8(self.0.len() as u8);
+        for motor in &self.0 {
+            w.write_u32(motor.rpm)
+                .write_u16(motor.invalid_percent)
+                .write_u8(motor.temperature)
+                .write_u16(motor.voltage)
+                .write_u16(motor.current)
+                .write_u16(motor.consumption);
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// Reason the flight controller refuses to arm, one per bit of the arming disable flags in
+/// `MSP_STATUS_EX`. The bits are in the Betaflight 4.4 order.
+#[cfg(feature = "betaflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArmingDisableFlag {
+    NoGyro,
+    Failsafe,
+    RxFailsafe,
+    NotDisarmed,
+    BoxFailsafe,
+    RunawayTakeoff,
+    CrashDetected,
+    Throttle,
+    Angle,
+    BootGraceTime,
+    NoPrearm,
+    Load,
+    Calibrating,
+    Cli,
+    CmsMenu,
+    Bst,
+    Msp,
+    Paralyze,
+    Gps,
+    RescueSw,
+    RpmFilter,
+    RebootRequired,
+    DshotBitbang,
+    AccCalibration,
+    MotorProtocol,
+    ArmSwitch,
+    /// A bit not known to this crate
+    Unknown(u8),
+}
+
+#[cfg(feature = "betaflight")]
+const ARMING_DISABLE_FLAGS: [ArmingDisableFlag; 26] = [
+    ArmingDisableFlag::NoGyro,
+    ArmingDisableFlag::Failsafe,
+    ArmingDisableFlag::RxFailsafe,
+    ArmingDisableFlag::NotDisarmed,
+    ArmingDisableFlag::BoxFailsafe,
+    ArmingDisableFlag::RunawayTakeoff,
+    ArmingDisableFlag::CrashDetected,
+    ArmingDisableFlag::Throttle,
+    ArmingDisableFlag::Angle,
+    ArmingDisableFlag::BootGraceTime,
+    ArmingDisableFlag::NoPrearm,
+    ArmingDisableFlag::Load,
+    ArmingDisableFlag::Calibrating,
+    ArmingDisableFlag::Cli,
+    ArmingDisableFlag::CmsMenu,
+    ArmingDisableFlag::Bst,
+    ArmingDisableFlag::Msp,
+    ArmingDisableFlag::Paralyze,
+    ArmingDisableFlag::Gps,
+    ArmingDisableFlag::RescueSw,
+    ArmingDisableFlag::RpmFilter,
+    ArmingDisableFlag::RebootRequired,
+    ArmingDisableFlag::DshotBitbang,
+    ArmingDisableFlag::AccCalibration,
+    ArmingDisableFlag::MotorProtocol,
+    ArmingDisableFlag::ArmSwitch,
+];
+
+#[cfg(feature = "betaflight")]
+impl From<u8> for ArmingDisableFlag {
+    /// Convert a bit number
+    fn from(bit: u8) -> Self {
+        ARMING_DISABLE_FLAGS
+            .get(bit as usize)
+            .cloned()
+            .unwrap_or(ArmingDisableFlag::Unknown(bit))
+    }
+}
+
+/// `MSP_STATUS_EX` response, in the Betaflight layout
+#[cfg(feature = "betaflight")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MspStatusEx {
+    /// microseconds
+    pub cycle_time: u16,
+    pub i2c_errors: u16,
+    /// Bitmask of the detected sensors
+    pub sensors: u16,
+    /// The first 32 flight mode flags
+    pub flight_mode: u32,
+    pub pid_profile: u8,
+    /// percent
+    pub system_load: u16,
+    pub pid_profile_count: u8,
+    pub rate_profile: u8,
+    /// Flight mode flags past the first 32
+    pub extra_flight_mode: Vec<u8>,
+    /// Number of arming disable flags the firmware defines
+    pub arming_disable_flags_count: u8,
+    pub arming_disable_flags: u32,
+    /// Configuration state, such as whether a reboot is required. Sent by Betaflight 3.4 on.
+    pub config_state: Option<u8>,
+}
+
+#[cfg(feature = "betaflight")]
+impl MspStatusEx {
+    /// The reasons the flight controller refuses to arm, in bit order. Empty when it is ready
+    /// to arm.
+    pub fn arming_disable_reasons(&self) -> Vec<ArmingDisableFlag> {
+        (0..32u8)
+            .filter(|bit| self.arming_disable_flags & (1 << bit) != 0)
+            .map(ArmingDisableFlag::from)
+            .collect()
+    }
+}
+
+#[cfg(feature = "betaflight")]
+impl MspPayload for MspStatusEx {
+    const COMMAND: u16 = MspCommandCode::MSP_STATUS_EX as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspStatusEx {
+            cycle_time: r.read_u16()?,
+            i2c_errors: r.read_u16()?,
+            sensors: r.read_u16()?,
+            flight_mode: r.read_u32()?,
+            pid_profile: r.read_u8()?,
+            system_load: r.read_u16()?,
+            pid_profile_count: r.read_u8()?,
+            rate_profile: r.read_u8()?,
+            // the extra flight mode bytes are led by their count
+            extra_flight_mode: {
+                let len = r.read_u8()? as usize;
+                r.read_bytes(len)?.to_vec()
+            },
+            arming_disable_flags_count: r.read_u8()?,
+            arming_disable_flags: r.read_u32()?,
+            config_state: r.read_u8().ok(),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        if self.extra_flight_mode.len() > 0xff {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        let mut w = PayloadWriter::with_capacity(22 + self.extra_flight_mode.len());
+        w.write_u16(self.cycle_time)
+            .write_u16(self.i2c_errors)
+            .write_u16(self.sensors)
+            .write_u32(self.flight_mode)
+            .write_u8(self.pid_profile)
+            .write_u16(self.system_load)
+            .write_u8(self.pid_profile_count)
+            .write_u8(self.rate_profile)
+            .write_u8(self.extra_flight_mode.len() as u8)
+            .write_bytes(&self.extra_flight_mode)
+            .write_u8(self.arming_disable_flags_count)
+            .write_u32(self.arming_disable_flags);
+        if let Some(config_state) = self.config_state {
+            w.write_u8(config_state);
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// Frequencies in MHz of the standard 5.8GHz bands, in Betaflight's band order: A, B, E,
+/// Fatshark and Raceband
+#[cfg(feature = "betaflight")]
+const VTX_FREQUENCIES: [[u16; 8]; 5] = [
+    [5865, 5845, 5825, 5805, 5785, 5765, 5745, 5725],
+    [5733, 5752, 5771, 5790, 5809, 5828, 5847, 5866],
+    [5705, 5685, 5665, 5645, 5885, 5905, 5925, 5945],
+    [5740, 5760, 5780, 5800, 5820, 5840, 5860, 5880],
+    [5658, 5695, 5732, 5769, 5806, 5843, 5880, 5917],
+];
+
+/// Frequency in MHz of a 1 based band and channel in the standard 5.8GHz band table. `None`
+/// for a band or channel outside of it, as used by VTX tables with custom bands.
+#[cfg(feature = "betaflight")]
+pub fn vtx_frequency(band: u8, channel: u8) -> Option<u16> {
+    VTX_FREQUENCIES
+        .get((band as usize).checked_sub(1)?)?
+        .get((channel as usize).checked_sub(1)?)
+        .cloned()
+}
+
+/// `MSP_VTX_CONFIG` response. Firmware has appended fields over time, so the later ones are
+/// only present when the payload is long enough:
+///
+/// * 5 bytes: device type through pit mode
+/// * 7 bytes: `frequency`
+/// * 8 bytes: `device_ready`
+/// * 9 bytes: `low_power_disarm`
+/// * 11 bytes: `pit_mode_frequency`
+/// * 15 bytes: the VTX table fields
+#[cfg(feature = "betaflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspVtxConfig {
+    /// VTX device type, 0xff when no VTX is configured
+    pub device_type: u8,
+    /// 1 based band, 0 when the frequency is set directly
+    pub band: u8,
+    /// 1 based channel within the band
+    pub channel: u8,
+    /// 1 based power level
+    pub power: u8,
+    pub pit_mode: bool,
+    /// MHz
+    pub frequency: Option<u16>,
+    pub device_ready: Option<bool>,
+    pub low_power_disarm: Option<u8>,
+    /// MHz
+    pub pit_mode_frequency: Option<u16>,
+    pub vtx_table_available: Option<bool>,
+    pub vtx_table_bands: Option<u8>,
+    pub vtx_table_channels: Option<u8>,
+    pub vtx_table_power_levels: Option<u8>,
+}
+
+#[cfg(feature = "betaflight")]
+impl MspVtxConfig {
+    /// Frequency in MHz, as reported by the firmware, or looked up from the band and channel
+    /// for older firmware that does not report it
+    pub fn frequency_mhz(&self) -> Option<u16> {
+        self.frequency
+            .or_else(|| vtx_frequency(self.band, self.channel))
+    }
+}
+
+#[cfg(feature = "betaflight")]
+impl MspPayload for MspVtxConfig {
+    const COMMAND: u16 = MspCommandCode::MSP_VTX_CONFIG as u16;
+
+    /// Any bytes after the VTX table fields are ignored
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        let device_type = r.read_u8()?;
+        let band = r.read_u8()?;
+        let channel = r.read_u8()?;
+        let power = r.read_u8()?;
+        let pit_mode = r.read_u8()? != 0;
+
+        // each optional group is read whole, and only if the previous one was present
+        let frequency = r.read_u16().ok();
+        let device_ready = frequency.and_then(|_| r.read_u8().ok());
+        let low_power_disarm = device_ready.and_then(|_| r.read_u8().ok());
+        let pit_mode_frequency = low_power_disarm.and_then(|_| r.read_u16().ok());
+        let vtx_table = pit_mode_frequency.and_then(|_| r.read_bytes(4).ok());
+
+        Ok(MspVtxConfig {
+            device_type,
+            band,
+            channel,
+            power,
+            pit_mode,
+            frequency,
+            device_ready: device_ready.map(|b| b != 0),
+            low_power_disarm,
+            pit_mode_frequency,
+            vtx_table_available: vtx_table.map(|b| b[0] != 0),
+            vtx_table_bands: vtx_table.map(|b| b[1]),
+            vtx_table_channels: vtx_table.map(|b| b[2]),
+            vtx_table_power_levels: vtx_table.map(|b| b[3]),
+        })
+    }
+
+    /// Writes the optional fields in order, up to the first group that is missing
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(15);
+        w.write_u8(self.device_type)
+            .write_u8(self.band)
+            .write_u8(self.channel)
+            .write_u8(self.power)
+            .write_u8(self.pit_mode as u8);
+
+        if let Some(frequency) = self.frequency {
+            w.write_u16(frequency);
+            if let Some(device_ready) = self.device_ready {
+                w.write_u8(device_ready as u8);
+                if let Some(low_power_disarm) = self.low_power_disarm {
+                    w.write_u8(low_power_disarm);
+                    if let Some(pit_mode_frequency) = self.pit_mode_frequency {
+                        w.write_u16(pit_mode_frequency);
+                        if let (Some(available), Some(bands), Some(channels), Some(power_levels)) = (
+                            self.vtx_table_available,
+                            self.vtx_table_bands,
+                            self.vtx_table_channels,
+                            self.vtx_table_power_levels,
+                        ) {
+                            w.write_u8(available as u8)
+                                .write_u8(bands)
+                                .write_u8(channels)
+                                .write_u8(power_levels);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_SET_VTX_CONFIG` request. `frequency` is either a frequency in MHz or, when below 64, a
+/// band and channel packed as `(band - 1) * 8 + (channel - 1)`. The fields after the pit mode
+/// are optional and only written up to the first one that is missing; the VTX table fields
+/// newer firmware accepts after them are not supported.
+#[cfg(feature = "betaflight")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspSetVtxConfig {
+    pub frequency: u16,
+    /// 1 based power level
+    pub power: u8,
+    pub pit_mode: bool,
+    pub low_power_disarm: Option<u8>,
+    /// MHz
+    pub pit_mode_frequency: Option<u16>,
+}
+
+#[cfg(feature = "betaflight")]
+impl MspPayload for MspSetVtxConfig {
+    const COMMAND: u16 = MspCommandCode::MSP_SET_VTX_CONFIG as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspSetVtxConfig {
+            frequency: r.read_u16()?,
+            power: r.read_u8()?,
+            pit_mode: r.read_u8()? != 0,
+            low_power_disarm: r.read_u8().ok(),
+            pit_mode_frequency: r.read_u16().ok(),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(7);
+        w.write_u16(self.frequency)
+            .write_u8(self.power)
+            .write_u8(self.pit_mode as u8);
+        if let Some(low_power_disarm) = self.low_power_disarm {
+            w.write_u8(low_power_disarm);
+            if let Some(frequency) = self.pit_mode_frequency {
+                w.write_u16(frequency);
+            }
+        }
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP_DISPLAYPORT` message, drawing a remote OSD. The first payload byte selects the
+/// subcommand, followed by its arguments.
+#[cfg(feature = "betaflight")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum MspDisplayPort {
+    /// Keeps the display attached
+    Heartbeat,
+    /// Hands the display back to the local OSD
+    Release,
+    ClearScreen,
+    /// Write `text` starting at `row` and `col`. The characters are indices into the OSD font,
+    /// which matches ASCII for plain text.
+    WriteString {
+        row: u8,
+        col: u8,
+        /// Font page and blink bits
+        attribute: u8,
+        text: Vec<u8>,
+    },
+    /// Show everything written since the last clear
+    DrawScreen,
+    Options {
+        font: u8,
+        resolution: u8,
+    },
+    /// Draw a system element, such as the stick overlay, rendered by the display itself
+    Sys {
+        row: u8,
+        col: u8,
+        element: u8,
+    },
+}
+
+#[cfg(feature = "betaflight")]
+impl MspPayload for MspDisplayPort {
+    const COMMAND: u16 = MspCommandCode::MSP_DISPLAYPORT as u16;
+
+    /// Fails with `InvalidData` on an unknown subcommand or missing arguments
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(match r.read_u8()? {
+            0 => MspDisplayPort::Heartbeat,
+            1 => MspDisplayPort::Release,
+            2 => MspDisplayPort::ClearScreen,
+            3 => MspDisplayPort::WriteString {
+                row: r.read_u8()?,
+                col: r.read_u8()?,
+                attribute: r.read_u8()?,
+                text: r.read_bytes(r.remaining())?.to_vec(),
+            },
+            4 => MspDisplayPort::DrawScreen,
+            5 => MspDisplayPort::Options {
+                font: r.read_u8()?,
+                resolution: r.read_u8()?,
+            },
+            6 => MspDisplayPort::Sys {
+                row: r.read_u8()?,
+                col: r.read_u8()?,
+                element: r.read_u8()?,
+            },
+            _ => return Err(MspPacketParseError::InvalidData),
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::new();
+        match self {
+            MspDisplayPort::Heartbeat => w.write_u8(0),
+            MspDisplayPort::Release => w.write_u8(1),
+            MspDisplayPort::ClearScreen => w.write_u8(2),
+            MspDisplayPort::WriteString {
+                row,
+                col,
+                attribute,
+                text,
+            } => w
+                .write_u8(3)
+                .write_u8(*row)
+                .write_u8(*col)
+                .write_u8(*attribute)
+                .write_bytes(text),
+            MspDisplayPort::DrawScreen => w.write_u8(4),
+            MspDisplayPort::Options { font, resolution } => {
+                w.write_u8(5).write_u8(*font).write_u8(*resolution)
+            }
+            MspDisplayPort::Sys { row, col, element } => w
+                .write_u8(6)
+                .write_u8(*row)
+                .write_u8(*col)
+                .write_u8(*element),
+        };
+        Ok(w.into_vec())
+    }
+}
+
+/// Navigation mode reported in `MSP_NAV_STATUS`
+#[cfg(feature = "inav")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MspNavMode {
+    None,
+    Hold,
+    Rth,
+    Nav,
+    Emergency,
+    Unknown(u8),
+}
+
+#[cfg(feature = "inav")]
+impl From<u8> for MspNavMode {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => MspNavMode::None,
+            1 => MspNavMode::Hold,
+            2 => MspNavMode::Rth,
+            3 => MspNavMode::Nav,
+            15 => MspNavMode::Emergency,
+            v => MspNavMode::Unknown(v),
+        }
+    }
+}
+
+#[cfg(feature = "inav")]
+impl From<MspNavMode> for u8 {
+    fn from(mode: MspNavMode) -> Self {
+        match mode {
+            MspNavMode::None => 0,
+            MspNavMode::Hold => 1,
+            MspNavMode::Rth => 2,
+            MspNavMode::Nav => 3,
+            MspNavMode::Emergency => 15,
+            MspNavMode::Unknown(v) => v,
+        }
+    }
+}
+
+/// Navigation state reported in `MSP_NAV_STATUS`
+#[cfg(feature = "inav")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MspNavState {
+    None,
+    RthStart,
+    RthEnroute,
+    HoldInfinite,
+    HoldTimed,
+    WpEnroute,
+    ProcessNext,
+    DoJump,
+    LandStart,
+    LandInProgress,
+    Landed,
+    LandSettle,
+    LandStartDescent,
+    HoverAboveHome,
+    EmergencyLanding,
+    RthClimb,
+    Unknown(u8),
+}
+
+#[cfg(feature = "inav")]
+const NAV_STATES: [MspNavState; 16] = [
+    MspNavState::None,
+    MspNavState::RthStart,
+    MspNavState::RthEnroute,
+    MspNavState::HoldInfinite,
+    MspNavState::HoldTimed,
+    MspNavState::WpEnroute,
+    MspNavState::ProcessNext,
+    MspNavState::DoJump,
+    MspNavState::LandStart,
+    MspNavState::LandInProgress,
+    MspNavState::Landed,
+    MspNavState::LandSettle,
+    MspNavState::LandStartDescent,
+    MspNavState::HoverAboveHome,
+    MspNavState::EmergencyLanding,
+    MspNavState::RthClimb,
+];
+
+#[cfg(feature = "inav")]
+impl From<u8> for MspNavState {
+    fn from(value: u8) -> Self {
+        NAV_STATES
+            .get(value as usize)
+            .cloned()
+            .unwrap_or(MspNavState::Unknown(value))
+    }
+}
+
+#[cfg(feature = "inav")]
+impl From<MspNavState> for u8 {
+    fn from(state: MspNavState) -> Self {
+        match state {
+            MspNavState::Unknown(v) => v,
+            s => NAV_STATES.iter().position(|n| *n == s).unwrap_or(0) as u8,
+        }
+    }
+}
+
+/// `MSP_NAV_STATUS` response, in the INAV layout
+#[cfg(feature = "inav")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspNavStatus {
+    pub mode: MspNavMode,
+    pub state: MspNavState,
+    /// Action of the active waypoint
+    pub active_wp_action: u8,
+    /// Number of the active waypoint
+    pub active_wp_number: u8,
+    /// Navigation error code, zero when there is none
+    pub error: u8,
+    /// degrees
+    pub target_heading: i16,
+}
+
+#[cfg(feature = "inav")]
+impl MspPayload for MspNavStatus {
+    const COMMAND: u16 = MspCommandCode::MSP_NAV_STATUS as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspNavStatus {
+            mode: r.read_u8()?.into(),
+            state: r.read_u8()?.into(),
+            active_wp_action: r.read_u8()?,
+            active_wp_number: r.read_u8()?,
+            error: r.read_u8()?,
+            target_heading: r.read_i16()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(7);
+        w.write_u8(self.mode.into())
+            .write_u8(self.state.into())
+            .write_u8(self.active_wp_action)
+            .write_u8(self.active_wp_number)
+            .write_u8(self.error)
+            .write_i16(self.target_heading);
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP2_SENSOR_RANGEFINDER` message, sent unsolicited by an external rangefinder
+#[cfg(feature = "inav")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspSensorRangefinder {
+    /// 0 to 255, higher is better
+    pub quality: u8,
+    /// millimeters, negative when out of range
+    pub distance_mm: i32,
+}
+
+#[cfg(feature = "inav")]
+impl MspPayload for MspSensorRangefinder {
+    const COMMAND: u16 = MspCommandCode::MSP2_SENSOR_RANGEFINDER as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspSensorRangefinder {
+            quality: r.read_u8()?,
+            distance_mm: r.read_i32()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(5);
+        w.write_u8(self.quality).write_i32(self.distance_mm);
+        Ok(w.into_vec())
+    }
+}
+
+/// `MSP2_SENSOR_GPS` message, sent unsolicited by an external GPS
+#[cfg(feature = "inav")]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MspSensorGps {
+    /// Sensor instance, for setups with several GPS units
+    pub instance: u8,
+    /// 0xffff when not available
+    pub gps_week: u16,
+    /// Time of week, milliseconds
+    pub ms_tow: u32,
+    pub fix_type: u8,
+    pub satellites_in_view: u8,
+    /// cm
+    pub horizontal_pos_accuracy: u16,
+    /// cm
+    pub vertical_pos_accuracy: u16,
+    /// cm/s
+    pub horizontal_vel_accuracy: u16,
+    pub hdop: u16,
+    /// 1e-7 degrees
+    pub longitude: i32,
+    /// 1e-7 degrees
+    pub latitude: i32,
+    /// cm above mean sea level
+    pub msl_altitude: i32,
+    /// cm/s
+    pub ned_vel_north: i32,
+    /// cm/s
+    pub ned_vel_east: i32,
+    /// cm/s
+    pub ned_vel_down: i32,
+    /// 0.01 degrees
+    pub ground_course: u16,
+    /// 0.01 degrees, 0xffff when not available
+    pub true_yaw: u16,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub min: u8,
+    pub sec: u8,
+}
+
+#[cfg(feature = "inav")]
+impl MspPayload for MspSensorGps {
+    const COMMAND: u16 = MspCommandCode::MSP2_SENSOR_GPS as u16;
+
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError> {
+        let mut r = PayloadReader::new(data);
+        Ok(MspSensorGps {
+            instance: r.read_u8()?,
+            gps_week: r.read_u16()?,
+            ms_tow: r.read_u32()?,
+            fix_type: r.read_u8()?,
+            satellites_in_view: r.read_u8()?,
+            horizontal_pos_accuracy: r.read_u16()?,
+            vertical_pos_accuracy: r.read_u16()?,
+            horizontal_vel_accuracy: r.read_u16()?,
+            hdop: r.read_u16()?,
+            longitude: r.read_i32()?,
+            latitude: r.read_i32()?,
+            msl_altitude: r.read_i32()?,
+            ned_vel_north: r.read_i32()?,
+            ned_vel_east: r.read_i32()?,
+            ned_vel_down: r.read_i32()?,
+            ground_course: r.read_u16()?,
+            true_yaw: r.read_u16()?,
+            year: r.read_u16()?,
+            month: r.read_u8()?,
+            day: r.read_u8()?,
+            hour: r.read_u8()?,
+            min: r.read_u8()?,
+            sec: r.read_u8()?,
+        })
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError> {
+        let mut w = PayloadWriter::with_capacity(52);
+        w.write_u8(self.instance)
+            .write_u16(self.gps_week)
+            .write_u32(self.ms_tow)
+            .write_u8(self.fix_type)
+            .write_u8(self.satellites_in_view)
+            .write_u16(self.horizontal_pos_accuracy)
+            .write_u16(self.vertical_pos_accuracy)
+            .write_u16(self.horizontal_vel_accuracy)
+            .write_u16(self.hdop)
+            .write_i32(self.longitude)
+            .write_i32(self.latitude)
+            .write_i32(self.msl_altitude)
+            .write_i32(self.ned_vel_north)
+            .write_i32(self.ned_vel_east)
+            .write_i32(self.ned_vel_down)
+            .write_u16(self.ground_course)
+            .write_u16(self.true_yaw)
+            .write_u16(self.year)
+            .write_bytes(&[self.month, self.day, self.hour, self.min, self.sec]);
+        Ok(w.into_vec())
+    }
+}
+
+#[test]
+fn test_handshake() {
+    let api = MspApiVersion::decode(&[0, 1, 44]).unwrap();
+    assert_eq!(
+        MspApiVersion {
+            protocol: 0,
+            major: 1,
+            minor: 44,
+        },
+        api
+    );
+    assert_eq!(vec![0, 1, 44], api.encode().unwrap());
+
+    let variant = MspFcVariant::decode(b"BTFL").unwrap();
+    assert_eq!(MspFcVariant("BTFL".to_owned()), variant);
+    assert_eq!(b"BTFL".to_vec(), variant.encode().unwrap());
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspFcVariant::decode(b"INA")
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspFcVariant::decode(&[b'I', b'N', b'A', 0xd6])
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspFcVariant("INAV2".to_owned()).encode()
+    );
+
+    let version = MspFcVersion::decode(&[4, 3, 1]).unwrap();
+    assert_eq!(
+        MspFcVersion {
+            major: 4,
+            minor: 3,
+            patch: 1,
+        },
+        version
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspFcVersion::decode(&[4, 3])
+    );
+}
+
+#[test]
+fn test_board_info() {
+    // Betaflight style reply from an F405 target, followed by an empty board name
+    let data = [
+        b'M', b'K', b'F', b'4', 0x00, 0x00, 0x02, 0x37, 0x09, b'S', b'T', b'M', b'3', b'2', b'F',
+        b'4', b'0', b'5', 0x00,
+    ];
+    let info = MspBoardInfo::decode(&data).unwrap();
+    assert_eq!(
+        MspBoardInfo {
+            board_id: "MKF4".to_owned(),
+            hardware_revision: 0,
+            board_type: Some(2),
+            target_capabilities: Some(0x37),
+            target_name: Some("STM32F405".to_owned()),
+        },
+        info
+    );
+    assert_eq!(data[..18].to_vec(), info.encode().unwrap());
+
+    let legacy = MspBoardInfo::decode(&[b'S', b'P', b'E', b'V', 0x01, 0x00]).unwrap();
+    assert_eq!(None, legacy.board_type);
+    assert_eq!(1, legacy.hardware_revision);
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspBoardInfo::decode(&data[..12])
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspBoardInfo::decode(&[0xff, b'K', b'F', b'4', 0x00, 0x00])
+    );
+}
+
+#[test]
+fn test_build_info() {
+    let data = b"Nov 14 202310:25:31c37a7c9";
+    let info = MspBuildInfo::decode(data).unwrap();
+    assert_eq!(
+        MspBuildInfo {
+            date: "Nov 14 2023".to_owned(),
+            time: "10:25:31".to_owned(),
+            git_hash: "c37a7c9".to_owned(),
+        },
+        info
+    );
+    assert_eq!(data.to_vec(), info.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspBuildInfo::decode(&data[..20])
+    );
+}
+
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+#[test]
+fn test_craft_name() {
+    let name = MspCraftName::decode(b"QUAD\0\0").unwrap();
+    assert_eq!(MspCraftName("QUAD".to_owned()), name);
+    assert_eq!(b"QUAD".to_vec(), name.encode().unwrap());
+    assert_eq!(name, MspCraftName::decode(&name.encode().unwrap()).unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        MspCraftName("SEVENTEEN_LETTERS".to_owned()).encode()
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspCraftName::decode(&[b'Q', 0xff])
+    );
+
+    let set = MspSetName("QUAD".to_owned());
+    assert_eq!(b"QUAD".to_vec(), set.encode().unwrap());
+    assert_eq!(Ok(set), MspSetName::decode(b"QUAD\0"));
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        MspSetName("SEVENTEEN_LETTERS".to_owned()).encode()
+    );
+}
+
+#[cfg(any(feature = "betaflight", feature = "inav"))]
+#[test]
+fn test_craft_name_borrowed() {
+    let data = b"QUAD\0\0";
+    let name = MspCraftName::decode_borrowed(data).unwrap();
+    assert_eq!(MspCraftNameRef("QUAD"), name);
+    assert_eq!(
+        data.as_ptr(),
+        name.0.as_ptr(),
+        "the name points into the payload"
+    );
+}
+
+#[test]
+fn test_servo() {
+    let data = [0xdc, 0x05, 0xd0, 0x07];
+    let servo = MspServo::decode(&data).unwrap();
+    assert_eq!(MspServo(vec![1500, 2000]), servo);
+    assert_eq!(data.to_vec(), servo.encode().unwrap());
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspServo::decode(&data[..3])
+    );
+}
+
+#[test]
+fn test_servo_conf() {
+    let data = [
+        0xe8, 0x03, 0xd0, 0x07, 0xdc, 0x05, 100, 0, 0, 255, 0, 0, 0, 0, // servo 0
+        0x4c, 0x04, 0x6c, 0x07, 0xdc, 0x05, 0x9c, 0, 0, 255, 0, 0, 0, 0, // servo 1
+    ];
+    let conf = MspServoConf::decode(&data).unwrap();
+    assert_eq!(2, conf.0.len());
+    assert_eq!(
+        MspServoConfRecord {
+            min: 1000,
+            max: 2000,
+            middle: 1500,
+            rate: 100,
+            forward_from_channel: 255,
+            reversed_sources: 0,
+        },
+        conf.0[0]
+    );
+    assert_eq!(1100, conf.0[1].min);
+    assert_eq!(1900, conf.0[1].max);
+    assert_eq!(-100, conf.0[1].rate);
+    assert_eq!(data.to_vec(), conf.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        MspServoConf::decode(&data[..20])
+    );
+}
+
+#[test]
+fn test_comp_gps() {
+    let data = [0xe8, 0x03, 0x4c, 0xff, 0x01];
+    let gps = MspCompGps::decode(&data).unwrap();
+    assert_eq!(
+        MspCompGps {
+            distance_to_home_m: 1000,
+            direction_to_home_deg: -180,
+            gps_update: 1,
+        },
+        gps
+    );
+    assert_eq!(data.to_vec(), gps.encode().unwrap());
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspCompGps::decode(&data[..4])
+    );
+}
+
+#[test]
+fn test_altitude() {
+    let data = [0x39, 0x30, 0x00, 0x00, 0xce, 0xff];
+    let altitude = MspAltitude::decode(&data).unwrap();
+    assert_eq!(
+        MspAltitude {
+            estimated_alt_cm: 12345,
+            vario_cm_s: -50,
+        },
+        altitude
+    );
+    assert_eq!(data.to_vec(), altitude.encode().unwrap());
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspAltitude::decode(&data[..5])
+    );
+}
+
+#[cfg(feature = "cleanflight")]
+#[test]
+fn test_battery_state() {
+    let legacy = [4, 0xdc, 0x05, 168, 0x2c, 0x01, 0xf6, 0xff, 0x01];
+    let state = MspBatteryState::decode(&legacy).unwrap();
+    assert_eq!(
+        MspBatteryState {
+            cell_count: 4,
+            capacity: 1500,
+            voltage: 168,
+            mah_drawn: 300,
+            amperage: -10,
+            state: 1,
+            voltage_precise: None,
+        },
+        state
+    );
+    assert_eq!(&legacy[..], state.encode().unwrap().as_slice());
+
+    let mut extended = legacy.to_vec();
+    extended.extend_from_slice(&[0x8e, 0x06]);
+    let state = MspBatteryState::decode(&extended).unwrap();
+    assert_eq!(Some(1678), state.voltage_precise);
+    assert_eq!(extended, state.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspBatteryState::decode(&legacy[..8])
+    );
+}
+
+#[test]
+fn test_debug() {
+    let data = [0x01, 0x00, 0xff, 0xff, 0x00, 0x80, 0xe8, 0x03];
+    let debug = MspDebug::decode(&data).unwrap();
+    assert_eq!(MspDebug(vec![1, -1, -32768, 1000]), debug);
+    assert_eq!(data.to_vec(), debug.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspDebug::decode(&data[..7])
+    );
+}
+
+#[test]
+fn test_set_raw_gps() {
+    let fix = MspSetRawGps::from_degrees(2, 12, 46.0569, -14.5058, 295, 150).unwrap();
+    assert_eq!(460569000, fix.lat);
+    assert_eq!(-145058000, fix.lon);
+    assert_eq!(
+        vec![0x02, 0x0c, 0xa8, 0xb9, 0x73, 0x1b, 0x30, 0x97, 0x5a, 0xf7, 0x27, 0x01, 0x96, 0x00],
+        fix.encode().unwrap()
+    );
+    assert_eq!(fix, MspSetRawGps::decode(&fix.encode().unwrap()).unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspSetRawGps::from_degrees(2, 12, 91.0, 0.0, 0, 0)
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        coordinate_from_degrees(f64::NAN)
+    );
+}
+
+#[test]
+fn test_motor() {
+    for count in &[4, 8] {
+        let motors = MspMotor((0..*count).map(|i| 1000 + i * 100).collect());
+        let data = motors.encode().unwrap();
+        assert_eq!(count * 2, data.len() as u16);
+        assert_eq!(&[0xe8, 0x03, 0x4c, 0x04], &data[..4]);
+        assert_eq!(motors, MspMotor::decode(&data).unwrap());
+    }
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspMotor::decode(&[0xe8, 0x03, 0x4c])
+    );
+
+    let set = MspSetMotor(vec![1000, 1100]);
+    assert_eq!(vec![0xe8, 0x03, 0x4c, 0x04], set.encode().unwrap());
+    assert_eq!(Ok(set), MspSetMotor::decode(&[0xe8, 0x03, 0x4c, 0x04]));
+    assert_eq!(214, MspSetMotor::COMMAND);
+}
+
+#[cfg(feature = "cleanflight")]
+#[test]
+fn test_rc_tuning() {
+    let legacy = [100, 0, 70, 70, 60, 10, 50, 0, 0xdc, 0x05];
+    let tuning = MspRcTuning::decode(&legacy).unwrap();
+    assert_eq!(1500, tuning.tpa_breakpoint);
+    assert_eq!(None, tuning.yaw_expo);
+    assert_eq!(None, tuning.rate_limits);
+    assert_eq!(legacy.to_vec(), tuning.encode().unwrap());
+
+    let modern = [
+        100, 0, 70, 70, 60, 10, 50, 0, 0xdc, 0x05, 5, 110, 120, 15, 1, 80, 0x6a, 0x07, 0x6a, 0x07,
+        0x6a, 0x07, 0x03,
+    ];
+    let tuning = MspRcTuning::decode(&modern).unwrap();
+    assert_eq!(
+        MspRcTuning {
+            rc_rate: 100,
+            rc_expo: 0,
+            rate_roll: 70,
+            rate_pitch: 70,
+            rate_yaw: 60,
+            dyn_thr_pid: 10,
+            thr_mid: 50,
+            thr_expo: 0,
+            tpa_breakpoint: 1500,
+            yaw_expo: Some(5),
+            yaw_rc_rate: Some(110),
+            pitch_rc_rate: Some(120),
+            pitch_expo: Some(15),
+            throttle_limit_type: Some(1),
+            throttle_limit_percent: Some(80),
+            rate_limits: Some([1898, 1898, 1898]),
+        },
+        tuning
+    );
+    assert_eq!(modern[..22].to_vec(), tuning.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspRcTuning::decode(&legacy[..7])
+    );
+}
+
+#[cfg(feature = "betaflight")]
+#[test]
+fn test_motor_telemetry() {
+    let data = [
+        0x02, // two motors
+        0x10, 0x27, 0x00, 0x00, 0x32, 0x00, 0x2a, 0x9a, 0x06, 0xf4, 0x01, 0x64, 0x00, //
+        0x98, 0x3a, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x98, 0x06, 0x20, 0x03, 0x6e, 0x00,
+    ];
+    let telemetry = MspMotorTelemetry::decode(&data).unwrap();
+    assert_eq!(
+        MspMotorTelemetry(vec![
+            MspMotorTelemetryRecord {
+                rpm: 10000,
+                invalid_percent: 50,
+                temperature: 42,
+                voltage: 1690,
+                current: 500,
+                consumption: 100,
+            },
+            MspMotorTelemetryRecord {
+                rpm: 15000,
+                invalid_percent: 0,
+                temperature: 44,
+                voltage: 1688,
+                current: 800,
+                consumption: 110,
+            },
+        ]),
+        telemetry
+    );
+    assert_eq!(data.to_vec(), telemetry.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspMotorTelemetry::decode(&data[..data.len() - 1])
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspMotorTelemetry::decode(&[])
+    );
+}
+
+#[cfg(feature = "inav")]
+#[test]
+fn test_nav_status() {
+    // flying to waypoint 3 with a heading of 270 degrees
+    let data = [3, 5, 1, 3, 0, 0x0e, 0x01];
+    let status = MspNavStatus::decode(&data).unwrap();
+    assert_eq!(
+        MspNavStatus {
+            mode: MspNavMode::Nav,
+            state: MspNavState::WpEnroute,
+            active_wp_action: 1,
+            active_wp_number: 3,
+            error: 0,
+            target_heading: 270,
+        },
+        status
+    );
+    assert_eq!(data.to_vec(), status.encode().unwrap());
+
+    assert_eq!(MspNavMode::Emergency, MspNavMode::from(15));
+    assert_eq!(MspNavMode::Unknown(7), MspNavMode::from(7));
+    assert_eq!(MspNavState::RthClimb, MspNavState::from(15));
+    assert_eq!(MspNavState::Unknown(16), MspNavState::from(16));
+    assert_eq!(15, u8::from(MspNavState::RthClimb));
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspNavStatus::decode(&data[..6])
+    );
+}
+
+#[cfg(feature = "inav")]
+#[test]
+fn test_sensor_rangefinder() {
+    let data = [200, 0xd2, 0x04, 0x00, 0x00];
+    let rangefinder = MspSensorRangefinder::decode(&data).unwrap();
+    assert_eq!(
+        MspSensorRangefinder {
+            quality: 200,
+            distance_mm: 1234,
+        },
+        rangefinder
+    );
+    assert_eq!(data.to_vec(), rangefinder.encode().unwrap());
+
+    // out of range
+    let out_of_range = MspSensorRangefinder::decode(&[0, 0xff, 0xff, 0xff, 0xff]).unwrap();
+    assert_eq!(-1, out_of_range.distance_mm);
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspSensorRangefinder::decode(&data[..4])
+    );
+}
+
+#[cfg(feature = "betaflight")]
+#[test]
+fn test_status_ex() {
+    let data = [
+        0x7d, 0x00, // cycle time
+        0x00, 0x00, // i2c errors
+        0x23, 0x00, // sensors
+        0x00, 0x00, 0x00, 0x00, // flight mode
+        0x00, // pid profile
+        0x05, 0x00, // system load
+        0x04, // pid profile count
+        0x01, // rate profile
+        0x01, 0x00, // one extra flight mode byte
+        0x1a, // arming disable flags count
+        0x80, 0x00, 0x00, 0x02, // throttle and arm switch
+        0x00, // config state
+    ];
+    let status = MspStatusEx::decode(&data).unwrap();
+    assert_eq!(125, status.cycle_time);
+    assert_eq!(vec![0x00], status.extra_flight_mode);
+    assert_eq!(Some(0), status.config_state);
+    assert_eq!(
+        vec![ArmingDisableFlag::Throttle, ArmingDisableFlag::ArmSwitch],
+        status.arming_disable_reasons()
+    );
+    assert_eq!(data.to_vec(), status.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspStatusEx::decode(&data[..20])
+    );
+}
+
+#[cfg(feature = "betaflight")]
+#[test]
+fn test_vtx_config() {
+    let data = [
+        0x03, // smartaudio
+        0x05, 0x01, // raceband, channel 1
+        0x02, // power level
+        0x00, // pit mode
+        0x1a, 0x16, // 5658MHz
+        0x01, // device ready
+        0x00, // low power disarm
+        0x00, 0x00, // pit mode frequency
+        0x01, 0x05, 0x08, 0x05, // vtx table
+    ];
+    let config = MspVtxConfig::decode(&data).unwrap();
+    assert_eq!(
+        MspVtxConfig {
+            device_type: 3,
+            band: 5,
+            channel: 1,
+            power: 2,
+            pit_mode: false,
+            frequency: Some(5658),
+            device_ready: Some(true),
+            low_power_disarm: Some(0),
+            pit_mode_frequency: Some(0),
+            vtx_table_available: Some(true),
+            vtx_table_bands: Some(5),
+            vtx_table_channels: Some(8),
+            vtx_table_power_levels: Some(5),
+        },
+        config
+    );
+    assert_eq!(data.to_vec(), config.encode().unwrap());
+
+    // older firmware does not report the frequency
+    let legacy = MspVtxConfig::decode(&data[..5]).unwrap();
+    assert_eq!(None, legacy.frequency);
+    assert_eq!(Some(5658), legacy.frequency_mhz());
+    assert_eq!(data[..5].to_vec(), legacy.encode().unwrap());
+    // a truncated group is dropped along with everything after it
+    assert_eq!(Ok(legacy), MspVtxConfig::decode(&data[..6]));
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspVtxConfig::decode(&data[..4])
+    );
+
+    assert_eq!(Some(5865), vtx_frequency(1, 1));
+    assert_eq!(Some(5917), vtx_frequency(5, 8));
+    assert_eq!(None, vtx_frequency(0, 1));
+    assert_eq!(None, vtx_frequency(6, 1));
+    assert_eq!(None, vtx_frequency(1, 9));
+
+    let set = MspSetVtxConfig {
+        // raceband, channel 1
+        frequency: 32,
+        power: 2,
+        pit_mode: true,
+        low_power_disarm: Some(1),
+        pit_mode_frequency: None,
+    };
+    let data = set.encode().unwrap();
+    assert_eq!(vec![0x20, 0x00, 0x02, 0x01, 0x01], data);
+    assert_eq!(Ok(set), MspSetVtxConfig::decode(&data));
+}
+
+#[test]
+fn test_action_requests() {
+    let packet = MspPacket::from(MspEepromWrite);
+    assert_eq!(250, packet.cmd);
+    assert!(packet.data.is_empty());
+    assert_eq!(
+        vec![b'$', b'M', b'<', 0, 250, 250],
+        Vec::from(packet.serialize_array::<6>().unwrap().0)
+    );
+    assert_eq!(Ok(MspEepromWrite), MspEepromWrite::decode(&[]));
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspEepromWrite::decode(&[0])
+    );
+
+    let packet = MspPacket::from(MspAccCalibration);
+    assert_eq!(205, packet.cmd);
+    assert!(packet.data.is_empty());
+    assert_eq!(Ok(vec![]), MspAccCalibration.encode());
+}
+
+#[cfg(feature = "cleanflight")]
+#[test]
+fn test_uid() {
+    let data = [
+        0x33, 0x00, 0x2d, 0x00, 0x0d, 0x51, 0x38, 0x34, 0x33, 0x38, 0x36, 0x36,
+    ];
+    let uid = MspUid::decode(&data).unwrap();
+    assert_eq!(MspUid([0x002d0033, 0x3438510d, 0x36363833]), uid);
+    assert_eq!("002d00333438510d36363833", uid.to_hex_string());
+    assert_eq!(data.to_vec(), uid.encode().unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspUid::decode(&data[..11])
+    );
+}
+
+#[cfg(feature = "cleanflight")]
+#[test]
+fn test_sensor_alignment() {
+    let data = [0x02, 0x08, 0x0b, 0x00, 0x01, 0x01];
+    let alignment = MspSensorAlignment::decode(&data).unwrap();
+    assert_eq!(
+        MspSensorAlignment {
+            gyro: MspSensorAlign::Cw90,
+            acc: MspSensorAlign::Cw270Flip,
+            mag: MspSensorAlign::Unknown(11),
+        },
+        alignment
+    );
+    assert_eq!(data[..3].to_vec(), alignment.encode().unwrap());
+
+    for code in 0..=255 {
+        assert_eq!(code, u8::from(MspSensorAlign::from(code)));
+    }
+
+    let set = MspSetSensorAlignment::from(alignment);
+    assert_eq!(
+        Ok(set),
+        MspSetSensorAlignment::decode(&set.encode().unwrap())
+    );
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspSensorAlignment::decode(&data[..2])
+    );
+}
+
+#[cfg(feature = "betaflight")]
+#[test]
+fn test_display_port() {
+    let write = MspDisplayPort::WriteString {
+        row: 2,
+        col: 10,
+        attribute: 0x80,
+        text: b"ARMED".to_vec(),
+    };
+    let data = write.encode().unwrap();
+    assert_eq!(vec![3, 2, 10, 0x80, b'A', b'R', b'M', b'E', b'D'], data);
+
+    let packet = MspPacket::request(MspCommandCode::MSP_DISPLAYPORT as u16).with_payload(data);
+    let mut frame = vec![];
+    packet.serialize_into(&mut frame).unwrap();
+    let parsed = MspPacket::parse_one(&frame).unwrap();
+    assert_eq!(Ok(write), parsed.payload_as::<MspDisplayPort>());
+
+    for message in &[
+        MspDisplayPort::Heartbeat,
+        MspDisplayPort::ClearScreen,
+        MspDisplayPort::DrawScreen,
+        MspDisplayPort::Options {
+            font: 1,
+            resolution: 2,
+        },
+        MspDisplayPort::Sys {
+            row: 0,
+            col: 3,
+            element: 1,
+        },
+    ] {
+        assert_eq!(
+            Ok(message.clone()),
+            MspDisplayPort::decode(&message.encode().unwrap())
+        );
+    }
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspDisplayPort::decode(&[])
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspDisplayPort::decode(&[3, 2, 10])
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        MspDisplayPort::decode(&[7])
+    );
+}
@@ -1,20 +1,59 @@
-use crc_any::CRCu8;
 use prelude::v1::*;
 
+use commands::MspCommand;
+use payload::MspPayload;
+
+#[cfg(feature = "serde")]
+use serde::de::{self, Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::ser::{Serialize, Serializer};
+
 /// Packet parsing error
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MspPacketParseError {
     OutputBufferSizeMismatch,
-    CrcMismatch { expected: u8, calculated: u8 },
+    CrcMismatch {
+        expected: u8,
+        calculated: u8,
+    },
     InvalidData,
     InvalidHeader1,
     InvalidHeader2,
     InvalidDirection,
     InvalidDataLength,
+    IncompletePacket,
+    TrailingData,
+    UnexpectedVersion,
+    WrongCommand {
+        expected: u16,
+        actual: u16,
+    },
+    UnexpectedCommand {
+        cmd: u16,
+    },
+    UnsupportedCommand {
+        cmd: u16,
+    },
+    UnexpectedFlag {
+        flag: u8,
+    },
+    /// A transport error of the reader based APIs. Only the kind is kept, since `io::Error` is
+    /// neither `Clone` nor `PartialEq`.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Io(io::ErrorKind),
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for MspPacketParseError {
+    fn from(e: io::Error) -> Self {
+        MspPacketParseError::Io(e.kind())
+    }
 }
 
 /// Packet's desired destination
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum MspPacketDirection {
     /// Network byte '<'
     ToFlightController,
@@ -36,14 +75,53 @@ impl MspPacketDirection {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Serialized as the network direction character, `'<'`, `'>'` or `'!'`
+#[cfg(feature = "serde")]
+impl Serialize for MspPacketDirection {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.to_byte() as char)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for MspPacketDirection {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match char::deserialize(deserializer)? {
+            '<' => Ok(MspPacketDirection::ToFlightController),
+            '>' => Ok(MspPacketDirection::FromFlightController),
+            '!' => Ok(MspPacketDirection::Unsupported),
+            c => Err(de::Error::invalid_value(
+                de::Unexpected::Char(c),
+                &"one of '<', '>' or '!'",
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// A decoded MSP packet, with a command code, direction and payload
 pub struct MspPacket {
     pub cmd: u16,
     pub direction: MspPacketDirection,
+    /// Protocol version the packet was framed with. Packets tunneled through a V1 frame are
+    /// reported as V2.
+    pub version: MspVersion,
+    /// Flag byte of a V2 frame, reserved by MSP2 for extensions such as fragmentation. Zero for
+    /// V1 frames.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_zero"))]
+    pub flag: u8,
     pub data: Vec<u8>,
 }
 
+#[cfg(feature = "serde")]
+fn is_zero(flag: &u8) -> bool {
+    *flag == 0
+}
+
+/// V1 command code used to tunnel a complete V2 frame inside a V1 frame
+const MSP_V2_FRAME_ID: u16 = 255;
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 enum MspParserState {
     Header1,
@@ -59,23 +137,69 @@ enum MspParserState {
     Crc,
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-enum MSPVersion {
+/// MSP protocol version, as identified by the second header byte
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MspVersion {
+    /// Network byte 'M'
     V1,
+    /// Network byte 'X'
     V2,
 }
 
-#[derive(Debug)]
+/// How a slice of input passed to `MspParser::parse_slice` ended
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MspSliceStatus {
+    /// The input ended between frames
+    Complete,
+    /// The input ended inside a frame, which needs at least `needed` more bytes. The count is
+    /// exact once the frame's payload length has been received.
+    Incomplete { needed: usize },
+}
+
+/// Counters accumulated by a parser over its lifetime. They survive `MspParser::reset`, and are
+/// only cleared by `MspParser::hard_reset`. The counters wrap on overflow.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MspParserStats {
+    /// Packets successfully parsed
+    pub packets: u32,
+    /// Frames dropped because of a checksum mismatch
+    pub crc_errors: u32,
+    /// Frames dropped for any other reason
+    pub framing_errors: u32,
+}
+
+#[derive(Debug, Clone)]
 /// Parser that can find packets from a raw byte stream
 pub struct MspParser {
     state: MspParserState,
-    packet_version: MSPVersion,
+    packet_version: MspVersion,
     packet_direction: MspPacketDirection,
     packet_cmd: u16,
     packet_data_length_remaining: usize,
     packet_data: Vec<u8>,
     packet_crc: u8,
-    packet_crc_v2: CRCu8,
+    packet_crc_v2: u8,
+    packet_flag: u8,
+    crc_fn: Option<fn(&[u8]) -> u8>,
+    buffer_pool: Vec<Vec<u8>>,
+    buffer_pool_size: usize,
+    capacity_hint: usize,
+    /// Retained buffer for payloads of exactly `capacity_hint` bytes
+    hinted_buf: Vec<u8>,
+    /// Is `packet_data` the hinted buffer, with the header scratch buffer parked in its place?
+    hinted_buf_in_use: bool,
+    expected_version: Option<MspVersion>,
+    allowed_commands: Option<Vec<u16>>,
+    strict_flags: bool,
+    last_frame_tunneled: bool,
+    stats: MspParserStats,
+    #[cfg(feature = "std")]
+    read_buf: [u8; 64],
+    #[cfg(feature = "std")]
+    read_pos: usize,
+    #[cfg(feature = "std")]
+    read_len: usize,
 }
 
 impl MspParser {
@@ -83,13 +207,132 @@ impl MspParser {
     pub fn new() -> MspParser {
         Self {
             state: MspParserState::Header1,
-            packet_version: MSPVersion::V1,
+            packet_version: MspVersion::V1,
             packet_direction: MspPacketDirection::ToFlightController,
             packet_data_length_remaining: 0,
             packet_cmd: 0,
             packet_data: Vec::new(),
             packet_crc: 0,
-            packet_crc_v2: CRCu8::crc8dvb_s2(),
+            packet_crc_v2: 0,
+            packet_flag: 0,
+            crc_fn: None,
+            buffer_pool: Vec::new(),
+            buffer_pool_size: 0,
+            capacity_hint: 0,
+            hinted_buf: Vec::new(),
+            hinted_buf_in_use: false,
+            expected_version: None,
+            allowed_commands: None,
+            strict_flags: false,
+            last_frame_tunneled: false,
+            stats: MspParserStats::default(),
+            #[cfg(feature = "std")]
+            read_buf: [0; 64],
+            #[cfg(feature = "std")]
+            read_pos: 0,
+            #[cfg(feature = "std")]
+            read_len: 0,
+        }
+    }
+
+    /// Create a parser for a V1 only stream, rejecting V2 frames with `UnexpectedVersion`
+    pub fn new_v1() -> MspParser {
+        MspParser::new().with_expected_version(MspVersion::V1)
+    }
+
+    /// Create a parser for a V2 only stream, rejecting V1 frames with `UnexpectedVersion`
+    pub fn new_v2() -> MspParser {
+        MspParser::new().with_expected_version(MspVersion::V2)
+    }
+
+    /// Keep up to `pool_size` payload buffers handed back through `recycle`, reusing their
+    /// allocations for the payloads of later packets
+    pub fn with_pooled_buffers(mut self, pool_size: usize) -> MspParser {
+        self.buffer_pool = Vec::with_capacity(pool_size);
+        self.buffer_pool_size = pool_size;
+        self
+    }
+
+    /// Keep a buffer of `capacity` bytes for payloads declaring exactly that length, such as
+    /// fixed size telemetry. The buffer leaves with the packet; handing it back through
+    /// `recycle` lets the next matching payload reuse it without allocating.
+    pub fn with_capacity_hint(mut self, capacity: usize) -> MspParser {
+        self.capacity_hint = capacity;
+        self.hinted_buf = Vec::with_capacity(capacity);
+        self
+    }
+
+    /// Reject frames of any other protocol version with `UnexpectedVersion`. By default both
+    /// versions are accepted.
+    pub fn with_expected_version(mut self, version: MspVersion) -> MspParser {
+        self.expected_version = Some(version);
+        self
+    }
+
+    /// Reject complete frames whose command is not in `commands` with `UnexpectedCommand`.
+    /// The check is made once the checksum has been verified, so V2 frames tunneled in V1 are
+    /// matched on their inner command. By default all commands are accepted.
+    pub fn with_allowed_commands(mut self, commands: &[u16]) -> MspParser {
+        self.allowed_commands = Some(commands.to_vec());
+        self
+    }
+
+    /// Reject V2 frames carrying a non-zero flag with `UnexpectedFlag`, for endpoints that only
+    /// speak plain MSP2. The flag of a V2 frame tunneled in V1 is not checked. By default any
+    /// flag is accepted.
+    pub fn with_strict_flags(mut self, strict: bool) -> MspParser {
+        self.strict_flags = strict;
+        self
+    }
+
+    /// Check V2 frames with `crc_fn` instead of CRC-8/DVB-S2, for firmware forks with a
+    /// different checksum. `crc_fn` is given the bytes the checksum covers: the flag, the
+    /// little-endian command and payload length, and the payload.
+    pub fn with_crc_fn(mut self, crc_fn: fn(&[u8]) -> u8) -> MspParser {
+        self.crc_fn = Some(crc_fn);
+        self
+    }
+
+    /// Return a packet's payload to the parser so its allocation can be reused. A buffer large
+    /// enough for the capacity hint replaces a missing hinted buffer. Other buffers are dropped
+    /// if the pool is full, or if the parser was not created with a pool.
+    pub fn recycle(&mut self, mut data: Vec<u8>) {
+        if !self.hinted_buf_in_use
+            && self.hinted_buf.capacity() < self.capacity_hint
+            && data.capacity() >= self.capacity_hint
+        {
+            data.clear();
+            self.hinted_buf = data;
+        } else if self.buffer_pool.len() < self.buffer_pool_size {
+            data.clear();
+            self.buffer_pool.push(data);
+        }
+    }
+
+    /// Start a new payload, reusing the current buffer or a pooled one when available
+    fn begin_packet_data(&mut self, capacity: usize) {
+        if self.packet_data.capacity() == 0 {
+            if let Some(buf) = self.buffer_pool.pop() {
+                self.packet_data = buf;
+            }
+        }
+        self.packet_data.clear();
+        self.packet_data.reserve(capacity);
+    }
+
+    /// Make room for a payload of `len` bytes, swapping in the hinted buffer when `len` matches
+    /// the capacity hint
+    fn reserve_payload(&mut self, len: usize) {
+        self.packet_data.clear();
+        if len > 0
+            && len == self.capacity_hint
+            && !self.hinted_buf_in_use
+            && self.hinted_buf.capacity() >= len
+        {
+            mem::swap(&mut self.packet_data, &mut self.hinted_buf);
+            self.hinted_buf_in_use = true;
+        } else {
+            self.packet_data.reserve(len);
         }
     }
 
@@ -98,64 +341,130 @@ impl MspParser {
         self.state == MspParserState::Header1
     }
 
+    /// Was the last packet returned by `parse` a V2 frame tunneled in V1? Such packets are
+    /// reported as `MspVersion::V2`; a proxy can use this to forward them the way they arrived.
+    pub fn last_frame_tunneled(&self) -> bool {
+        self.last_frame_tunneled
+    }
+
+    /// Counters accumulated since the parser was created or last hard reset
+    pub fn stats(&self) -> MspParserStats {
+        self.stats
+    }
+
     /// Parse the next input byte. Returns a valid packet whenever a full packet is received, otherwise
     /// restarts the state of the parser.
     pub fn parse(&mut self, input: u8) -> Result<Option<MspPacket>, MspPacketParseError> {
+        let result = self.parse_byte(input);
+        match result {
+            Ok(Some(_)) => self.stats.packets = self.stats.packets.wrapping_add(1),
+            Ok(None) => {}
+            Err(MspPacketParseError::CrcMismatch { .. }) => {
+                self.stats.crc_errors = self.stats.crc_errors.wrapping_add(1)
+            }
+            Err(_) => self.stats.framing_errors = self.stats.framing_errors.wrapping_add(1),
+        }
+
+        #[cfg(feature = "tracing")]
+        match result {
+            Ok(Some(ref packet)) => ::tracing::debug!(
+                cmd = packet.cmd,
+                direction = ?packet.direction,
+                len = packet.data.len(),
+                "msp packet parsed"
+            ),
+            Ok(None) => {}
+            Err(ref e) => ::tracing::debug!(error = ?e, "msp parse error"),
+        }
+
+        result
+    }
+
+    /// Like `parse`, but stores a completed packet in `out` and returns whether one was
+    /// produced. `out` is only written when a packet completes, so a caller can keep reusing
+    /// the same slot in a tight loop.
+    pub fn parse_into(
+        &mut self,
+        input: u8,
+        out: &mut Option<MspPacket>,
+    ) -> Result<bool, MspPacketParseError> {
+        match self.parse(input)? {
+            Some(packet) => {
+                *out = Some(packet);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn parse_byte(&mut self, input: u8) -> Result<Option<MspPacket>, MspPacketParseError> {
         match self.state {
             MspParserState::Header1 => {
+                // noise between frames is skipped; the parser is already reset in this state
                 if input == b'$' {
                     self.state = MspParserState::Header2;
-                } else {
-                    self.reset();
                 }
             }
 
             MspParserState::Header2 => {
                 self.packet_version = match input as char {
-                    'M' => MSPVersion::V1,
-                    'X' => MSPVersion::V2,
+                    'M' => MspVersion::V1,
+                    'X' => MspVersion::V2,
                     _ => {
-                        self.reset();
+                        self.reset_frame();
                         return Err(MspPacketParseError::InvalidHeader2);
                     }
                 };
 
+                if let Some(expected) = self.expected_version {
+                    if expected != self.packet_version {
+                        self.reset_frame();
+                        return Err(MspPacketParseError::UnexpectedVersion);
+                    }
+                }
+
                 self.state = MspParserState::Direction;
             }
 
             MspParserState::Direction => {
                 match input {
-                    60 => self.packet_direction = MspPacketDirection::ToFlightController, // '>'
-                    62 => self.packet_direction = MspPacketDirection::FromFlightController, // '<'
+                    60 => self.packet_direction = MspPacketDirection::ToFlightController, // '<'
+                    62 => self.packet_direction = MspPacketDirection::FromFlightController, // '>'
                     33 => self.packet_direction = MspPacketDirection::Unsupported, // '!' error
                     _ => {
-                        self.reset();
+                        self.reset_frame();
                         return Err(MspPacketParseError::InvalidDirection);
                     }
                 }
 
                 self.state = match self.packet_version {
-                    MSPVersion::V1 => MspParserState::DataLength,
-                    MSPVersion::V2 => MspParserState::FlagV2,
+                    MspVersion::V1 => MspParserState::DataLength,
+                    MspVersion::V2 => MspParserState::FlagV2,
                 };
             }
 
             MspParserState::FlagV2 => {
                 // uint8, flag, usage to be defined (set to zero)
+                if self.strict_flags && input != 0 {
+                    self.reset_frame();
+                    return Err(MspPacketParseError::UnexpectedFlag { flag: input });
+                }
+
                 self.state = MspParserState::CommandV2;
-                self.packet_data = Vec::with_capacity(2);
-                self.packet_crc_v2.digest(&[input]);
+                self.packet_flag = input;
+                self.begin_packet_data(2);
+                self.update_crc_v2(&[input]);
             }
 
             MspParserState::CommandV2 => {
                 self.packet_data.push(input);
+                self.update_crc_v2(&[input]);
 
                 if self.packet_data.len() == 2 {
                     let mut s = [0u8; size_of::<u16>()];
                     s.copy_from_slice(&self.packet_data);
                     self.packet_cmd = u16::from_le_bytes(s);
 
-                    self.packet_crc_v2.digest(&self.packet_data);
                     self.packet_data.clear();
                     self.state = MspParserState::DataLengthV2;
                 }
@@ -163,14 +472,13 @@ impl MspParser {
 
             MspParserState::DataLengthV2 => {
                 self.packet_data.push(input);
+                self.update_crc_v2(&[input]);
 
                 if self.packet_data.len() == 2 {
                     let mut s = [0u8; size_of::<u16>()];
                     s.copy_from_slice(&self.packet_data);
                     self.packet_data_length_remaining = u16::from_le_bytes(s).into();
-                    self.packet_crc_v2.digest(&self.packet_data);
-                    self.packet_data =
-                        Vec::with_capacity(self.packet_data_length_remaining as usize);
+                    self.reserve_payload(self.packet_data_length_remaining);
 
                     if self.packet_data_length_remaining == 0 {
                         self.state = MspParserState::Crc;
@@ -182,7 +490,15 @@ impl MspParser {
 
             MspParserState::DataV2 => {
                 self.packet_data.push(input);
-                self.packet_data_length_remaining -= 1;
+                self.update_crc_v2(&[input]);
+                self.packet_data_length_remaining =
+                    match self.packet_data_length_remaining.checked_sub(1) {
+                        Some(remaining) => remaining,
+                        None => {
+                            self.reset_frame();
+                            return Err(MspPacketParseError::InvalidData);
+                        }
+                    };
 
                 if self.packet_data_length_remaining == 0 {
                     self.state = MspParserState::Crc;
@@ -193,7 +509,8 @@ impl MspParser {
                 self.packet_data_length_remaining = input as usize;
                 self.state = MspParserState::Command;
                 self.packet_crc ^= input;
-                self.packet_data = Vec::with_capacity(input as usize);
+                self.begin_packet_data(0);
+                self.reserve_payload(input as usize);
             }
 
             MspParserState::Command => {
@@ -210,7 +527,14 @@ impl MspParser {
 
             MspParserState::Data => {
                 self.packet_data.push(input);
-                self.packet_data_length_remaining -= 1;
+                self.packet_data_length_remaining =
+                    match self.packet_data_length_remaining.checked_sub(1) {
+                        Some(remaining) => remaining,
+                        None => {
+                            self.reset_frame();
+                            return Err(MspPacketParseError::InvalidData);
+                        }
+                    };
 
                 self.packet_crc ^= input;
 
@@ -220,14 +544,24 @@ impl MspParser {
             }
 
             MspParserState::Crc => {
-                if self.packet_version == MSPVersion::V2 {
-                    self.packet_crc_v2.digest(&self.packet_data);
-                    self.packet_crc = self.packet_crc_v2.get_crc();
+                if self.packet_version == MspVersion::V2 {
+                    self.packet_crc = match self.crc_fn {
+                        Some(crc_fn) => {
+                            let mut crc_input = Vec::with_capacity(5 + self.packet_data.len());
+                            crc_input.push(self.packet_flag);
+                            crc_input.extend_from_slice(&self.packet_cmd.to_le_bytes());
+                            crc_input
+                                .extend_from_slice(&(self.packet_data.len() as u16).to_le_bytes());
+                            crc_input.extend_from_slice(&self.packet_data);
+                            crc_fn(&crc_input)
+                        }
+                        None => self.packet_crc_v2,
+                    };
                 }
 
                 let packet_crc = self.packet_crc;
                 if input != packet_crc {
-                    self.reset();
+                    self.reset_frame();
                     return Err(MspPacketParseError::CrcMismatch {
                         expected: input,
                         calculated: packet_crc,
@@ -236,14 +570,48 @@ impl MspParser {
 
                 let mut n = Vec::new();
                 mem::swap(&mut self.packet_data, &mut n);
+                if self.hinted_buf_in_use {
+                    // keep the header scratch buffer for the next frame
+                    self.packet_data = mem::take(&mut self.hinted_buf);
+                    self.hinted_buf_in_use = false;
+                }
+
+                let mut cmd = self.packet_cmd;
+                let mut version = self.packet_version;
+                let mut flag = self.packet_flag;
+                let tunneled = version == MspVersion::V1 && cmd == MSP_V2_FRAME_ID;
+                if tunneled {
+                    match unwrap_v2_frame(&n, self.crc_fn.unwrap_or(crc8_dvb_s2_frame)) {
+                        Ok((inner_flag, inner_cmd, inner_data)) => {
+                            cmd = inner_cmd;
+                            version = MspVersion::V2;
+                            flag = inner_flag;
+                            n = inner_data;
+                        }
+                        Err(e) => {
+                            self.reset_frame();
+                            return Err(e);
+                        }
+                    }
+                }
+
+                if let Some(ref allowed) = self.allowed_commands {
+                    if !allowed.contains(&cmd) {
+                        self.reset_frame();
+                        return Err(MspPacketParseError::UnexpectedCommand { cmd });
+                    }
+                }
 
                 let packet = MspPacket {
-                    cmd: self.packet_cmd,
+                    cmd,
                     direction: self.packet_direction,
+                    version,
+                    flag,
                     data: n,
                 };
 
-                self.reset();
+                self.reset_frame();
+                self.last_frame_tunneled = tunneled;
 
                 return Ok(Some(packet));
             }
@@ -252,15 +620,374 @@ impl MspParser {
         Ok(None)
     }
 
+    /// Minimum number of bytes still needed to complete the frame in progress, zero when the
+    /// parser is between packets. Exact once the frame's payload length has been received.
+    pub fn bytes_needed(&self) -> usize {
+        match self.state {
+            MspParserState::Header1 => 0,
+            // the shortest frame is an empty V1 one
+            MspParserState::Header2 => 5,
+            MspParserState::Direction => match self.packet_version {
+                MspVersion::V1 => 4,
+                MspVersion::V2 => 7,
+            },
+            MspParserState::DataLength => 3,
+            MspParserState::Command => self.packet_data_length_remaining + 2,
+            MspParserState::FlagV2 => 6,
+            MspParserState::CommandV2 => 5 - self.packet_data.len(),
+            MspParserState::DataLengthV2 => 3 - self.packet_data.len(),
+            MspParserState::Data | MspParserState::DataV2 => self.packet_data_length_remaining + 1,
+            MspParserState::Crc => 1,
+        }
+    }
+
+    /// Parse a chunk of input bytes, such as a datagram, returning the packets completed by it
+    /// and whether it ended inside a frame. Stops at the first parse error, like `feed`.
+    pub fn parse_slice(
+        &mut self,
+        input: &[u8],
+    ) -> Result<(Vec<MspPacket>, MspSliceStatus), MspPacketParseError> {
+        let mut packets = Vec::new();
+        self.feed(input, |packet| packets.push(packet))?;
+
+        let status = match self.bytes_needed() {
+            0 => MspSliceStatus::Complete,
+            needed => MspSliceStatus::Incomplete { needed },
+        };
+        Ok((packets, status))
+    }
+
+    /// Parse a chunk of input bytes, calling `on_packet` for every packet completed by it. Stops
+    /// at the first parse error; packets completed before the failing byte have already been
+    /// delivered, and the bytes after it are not consumed.
+    pub fn feed<F: FnMut(MspPacket)>(
+        &mut self,
+        input: &[u8],
+        mut on_packet: F,
+    ) -> Result<(), MspPacketParseError> {
+        let mut i = 0;
+        while i < input.len() {
+            let consumed = self.consume_payload(&input[i..]);
+            if consumed > 0 {
+                i += consumed;
+                continue;
+            }
+
+            if let Some(packet) = self.parse(input[i])? {
+                on_packet(packet);
+            }
+            i += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Parse `input` up to the end of the first packet completed in it, returning the packet and
+    /// the bytes after it. When no packet completes, all of `input` is consumed and an empty
+    /// tail is returned. A parse error discards the frame it happened in, like `parse`.
+    pub fn parse_frame<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> Result<(Option<MspPacket>, &'a [u8]), MspPacketParseError> {
+        let mut i = 0;
+        while i < input.len() {
+            let consumed = self.consume_payload(&input[i..]);
+            if consumed > 0 {
+                i += consumed;
+                continue;
+            }
+
+            i += 1;
+            if let Some(packet) = self.parse(input[i - 1])? {
+                return Ok((Some(packet), &input[i..]));
+            }
+        }
+
+        Ok((None, &input[i..]))
+    }
+
+    /// Parse `input` up to and including the first byte that fails to parse. Returns the
+    /// packets completed before it, the number of bytes consumed, and the error, if any. The
+    /// parser is left ready for the next frame, so the caller can handle the error and resume
+    /// with `input[consumed..]`.
+    pub fn parse_until_error(
+        &mut self,
+        input: &[u8],
+    ) -> (Vec<MspPacket>, usize, Option<MspPacketParseError>) {
+        let mut packets = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            let consumed = self.consume_payload(&input[i..]);
+            if consumed > 0 {
+                i += consumed;
+                continue;
+            }
+
+            i += 1;
+            match self.parse(input[i - 1]) {
+                Ok(Some(packet)) => packets.push(packet),
+                Ok(None) => {}
+                Err(e) => return (packets, i, Some(e)),
+            }
+        }
+
+        (packets, i, None)
+    }
+
+    /// Lazily parse `input`, yielding every packet and parse error as the iterator is advanced.
+    /// Bytes of a packet left incomplete at the end of `input` stay in the parser, to be
+    /// completed by the next call.
+    pub fn parse_iter<'a>(
+        &'a mut self,
+        input: &'a [u8],
+    ) -> impl Iterator<Item = Result<MspPacket, MspPacketParseError>> + 'a {
+        let mut i = 0;
+        iter::from_fn(move || {
+            while i < input.len() {
+                let consumed = self.consume_payload(&input[i..]);
+                if consumed > 0 {
+                    i += consumed;
+                    continue;
+                }
+
+                i += 1;
+                match self.parse(input[i - 1]) {
+                    Ok(Some(packet)) => return Some(Ok(packet)),
+                    Ok(None) => {}
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            None
+        })
+    }
+
+    /// Parse `input`, returning every good packet in it. Corrupt frames are skipped and the
+    /// parser resyncs on the next header; the skipped frames are counted in the stats.
+    pub fn parse_all_lossy(&mut self, input: &[u8]) -> Vec<MspPacket> {
+        #[cfg(feature = "tracing")]
+        let before = self.stats;
+
+        let packets: Vec<_> = self.parse_iter(input).filter_map(Result::ok).collect();
+
+        #[cfg(feature = "tracing")]
+        {
+            let skipped = self
+                .stats
+                .crc_errors
+                .wrapping_sub(before.crc_errors)
+                .wrapping_add(
+                    self.stats
+                        .framing_errors
+                        .wrapping_sub(before.framing_errors),
+                );
+            if skipped > 0 {
+                ::tracing::debug!(skipped, packets = packets.len(), "msp frames skipped");
+            }
+        }
+
+        packets
+    }
+
+    /// Read from `r` until the next complete packet. Input is read in chunks, and the bytes
+    /// following the packet are kept for the next call. Parse errors are returned as they
+    /// happen; calling again resumes after the failing byte. A reader at end of file fails with
+    /// `Io(UnexpectedEof)`.
+    ///
+    /// The bytes read ahead are only used by later `parse_reader` calls, so call `reset` before
+    /// switching to `parse` or `feed`.
+    #[cfg(feature = "std")]
+    pub fn parse_reader<R: io::Read>(
+        &mut self,
+        r: &mut R,
+    ) -> Result<MspPacket, MspPacketParseError> {
+        loop {
+            while self.read_pos < self.read_len {
+                let b = self.read_buf[self.read_pos];
+                self.read_pos += 1;
+                if let Some(packet) = self.parse(b)? {
+                    return Ok(packet);
+                }
+            }
+
+            self.read_len = loop {
+                match r.read(&mut self.read_buf) {
+                    Ok(0) => return Err(MspPacketParseError::Io(io::ErrorKind::UnexpectedEof)),
+                    Ok(n) => break n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            self.read_pos = 0;
+        }
+    }
+
+    /// While in the middle of a payload, take as much of it from `input` as possible in one
+    /// go. Returns the number of bytes consumed, zero if the parser is not inside a payload.
+    fn consume_payload(&mut self, input: &[u8]) -> usize {
+        match self.state {
+            MspParserState::Data | MspParserState::DataV2
+                if self.packet_data_length_remaining > 0 => {}
+            _ => return 0,
+        }
+
+        let chunk = &input[..min(self.packet_data_length_remaining, input.len())];
+        self.packet_data.extend_from_slice(chunk);
+        match self.state {
+            MspParserState::Data => {
+                self.packet_crc = chunk.iter().fold(self.packet_crc, |crc, b| crc ^ b)
+            }
+            _ => self.update_crc_v2(chunk),
+        }
+
+        self.packet_data_length_remaining -= chunk.len();
+        if self.packet_data_length_remaining == 0 {
+            self.state = MspParserState::Crc;
+        }
+        chunk.len()
+    }
+
+    /// Feed V2 frame bytes to the running CRC-8/DVB-S2. Skipped with a custom `crc_fn`, which
+    /// is run over the whole frame once it is complete.
+    fn update_crc_v2(&mut self, bytes: &[u8]) {
+        if self.crc_fn.is_none() {
+            self.packet_crc_v2 = crc8_dvb_s2(self.packet_crc_v2, bytes);
+        }
+    }
+
+    /// Drop the frame in progress, and any input read ahead by `parse_reader`, and wait for the
+    /// next header. The stats and the parser's configuration are kept.
     pub fn reset(&mut self) {
+        self.reset_frame();
+        #[cfg(feature = "std")]
+        {
+            self.read_pos = 0;
+            self.read_len = 0;
+        }
+    }
+
+    /// Drop the frame in progress and wait for the next header. Called after every completed
+    /// packet and every error.
+    fn reset_frame(&mut self) {
         self.state = MspParserState::Header1;
         self.packet_direction = MspPacketDirection::ToFlightController;
         self.packet_data_length_remaining = 0;
         self.packet_cmd = 0;
         self.packet_data.clear();
+        if self.hinted_buf_in_use {
+            mem::swap(&mut self.packet_data, &mut self.hinted_buf);
+            self.hinted_buf_in_use = false;
+        }
         self.packet_crc = 0;
-        self.packet_crc_v2.reset();
+        self.packet_crc_v2 = 0;
+        self.packet_flag = 0;
+    }
+
+    /// Drop the frame in progress like `reset`, returning how many of its bytes had been
+    /// received. Zero when the parser is between packets.
+    pub fn drain_partial(&mut self) -> usize {
+        let received = match self.state {
+            MspParserState::Header1 => 0,
+            MspParserState::Header2 => 1,
+            MspParserState::Direction => 2,
+            MspParserState::DataLength | MspParserState::FlagV2 => 3,
+            MspParserState::Command => 4,
+            MspParserState::Data => 5 + self.packet_data.len(),
+            MspParserState::CommandV2 => 4 + self.packet_data.len(),
+            MspParserState::DataLengthV2 => 6 + self.packet_data.len(),
+            MspParserState::DataV2 => 8 + self.packet_data.len(),
+            MspParserState::Crc => match self.packet_version {
+                MspVersion::V1 => 5 + self.packet_data.len(),
+                MspVersion::V2 => 8 + self.packet_data.len(),
+            },
+        };
+
+        self.reset();
+        received
+    }
+
+    /// Like `reset`, but also clears the accumulated stats
+    pub fn hard_reset(&mut self) {
+        self.reset();
+        self.last_frame_tunneled = false;
+        self.stats = MspParserStats::default();
+    }
+}
+
+/// V1 checksum: XOR of the data length, the command and every payload byte
+pub fn crc_v1(data_len: u8, cmd: u8, payload: &[u8]) -> u8 {
+    payload.iter().fold(data_len ^ cmd, |crc, b| crc ^ b)
+}
+
+/// V2 checksum: CRC-8/DVB-S2 over the flag, the little-endian command, the little-endian
+/// payload length and the payload. The payload must fit in a V2 frame, at most 65535 bytes.
+pub fn crc_v2(flag: u8, cmd: u16, payload: &[u8]) -> u8 {
+    debug_assert!(payload.len() <= u16::MAX as usize);
+    let cmd = cmd.to_le_bytes();
+    let len = (payload.len() as u16).to_le_bytes();
+    let crc = crc8_dvb_s2(0, &[flag, cmd[0], cmd[1], len[0], len[1]]);
+    crc8_dvb_s2(crc, payload)
+}
+
+/// CRC-8/DVB-S2 (polynomial 0xd5) of every single byte value
+const CRC8_DVB_S2_TABLE: [u8; 256] = crc8_dvb_s2_table();
+
+const fn crc8_dvb_s2_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < table.len() {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0xd5
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Continue a CRC-8/DVB-S2 calculation over `data`
+fn crc8_dvb_s2(crc: u8, data: &[u8]) -> u8 {
+    data.iter()
+        .fold(crc, |crc, b| CRC8_DVB_S2_TABLE[(crc ^ b) as usize])
+}
+
+/// CRC-8/DVB-S2 of a whole buffer, the default V2 checksum function
+fn crc8_dvb_s2_frame(data: &[u8]) -> u8 {
+    crc8_dvb_s2(0, data)
+}
+
+/// Decode the V2 frame (flag, command, length, payload and CRC) carried as the payload of a V1
+/// frame with the `MSP_V2_FRAME_ID` command
+fn unwrap_v2_frame(
+    data: &[u8],
+    crc_fn: fn(&[u8]) -> u8,
+) -> Result<(u8, u16, Vec<u8>), MspPacketParseError> {
+    if data.len() < 6 {
+        return Err(MspPacketParseError::InvalidDataLength);
+    }
+
+    let cmd = u16::from_le_bytes([data[1], data[2]]);
+    let len = u16::from_le_bytes([data[3], data[4]]) as usize;
+    if data.len() != len + 6 {
+        return Err(MspPacketParseError::InvalidDataLength);
+    }
+
+    let calculated = crc_fn(&data[..len + 5]);
+    let expected = data[len + 5];
+    if expected != calculated {
+        return Err(MspPacketParseError::CrcMismatch {
+            expected,
+            calculated,
+        });
     }
+
+    Ok((data[0], cmd, data[5..len + 5].to_vec()))
 }
 
 impl Default for ::MspParser {
@@ -270,6 +997,253 @@ impl Default for ::MspParser {
 }
 
 impl MspPacket {
+    /// Parse a single, complete packet from a buffer, such as a received datagram. Fails if the
+    /// buffer does not start with the frame, if the frame is incomplete or if any bytes follow
+    /// it.
+    pub fn parse_one(bytes: &[u8]) -> Result<MspPacket, MspPacketParseError> {
+        if bytes.first().is_some_and(|b| *b != b'$') {
+            return Err(MspPacketParseError::InvalidHeader1);
+        }
+
+        let mut parser = MspParser::new();
+        for (i, b) in bytes.iter().enumerate() {
+            if let Some(packet) = parser.parse(*b)? {
+                if i + 1 != bytes.len() {
+                    return Err(MspPacketParseError::TrailingData);
+                }
+                return Ok(packet);
+            }
+        }
+
+        Err(MspPacketParseError::IncompletePacket)
+    }
+
+    /// Check that `bytes` holds exactly one well formed V1 or V2 frame, without allocating or
+    /// running a parser. Checks the header, the declared length and the CRC; the payload of a
+    /// tunneled V2 frame is not inspected.
+    pub fn validate_frame(bytes: &[u8]) -> Result<(), MspPacketParseError> {
+        if bytes.len() < 3 {
+            return Err(MspPacketParseError::IncompletePacket);
+        }
+        if bytes[0] != b'$' {
+            return Err(MspPacketParseError::InvalidHeader1);
+        }
+        let version = match bytes[1] {
+            b'M' => MspVersion::V1,
+            b'X' => MspVersion::V2,
+            _ => return Err(MspPacketParseError::InvalidHeader2),
+        };
+        match bytes[2] {
+            b'<' | b'>' | b'!' => {}
+            _ => return Err(MspPacketParseError::InvalidDirection),
+        }
+
+        let header_len = match version {
+            MspVersion::V1 => 5,
+            MspVersion::V2 => 8,
+        };
+        if bytes.len() < header_len {
+            return Err(MspPacketParseError::IncompletePacket);
+        }
+        let data_len = match version {
+            MspVersion::V1 => bytes[3] as usize,
+            MspVersion::V2 => u16::from_le_bytes([bytes[6], bytes[7]]) as usize,
+        };
+        let frame_len = header_len + data_len + 1;
+        if bytes.len() < frame_len {
+            return Err(MspPacketParseError::IncompletePacket);
+        }
+        if bytes.len() > frame_len {
+            return Err(MspPacketParseError::TrailingData);
+        }
+
+        let payload = &bytes[header_len..frame_len - 1];
+        let calculated = match version {
+            MspVersion::V1 => crc_v1(bytes[3], bytes[4], payload),
+            MspVersion::V2 => crc_v2(bytes[3], u16::from_le_bytes([bytes[4], bytes[5]]), payload),
+        };
+        let expected = bytes[frame_len - 1];
+        if expected != calculated {
+            return Err(MspPacketParseError::CrcMismatch {
+                expected,
+                calculated,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Build a request to the flight controller with an empty payload. The version is set to V1,
+    /// unless the command code only fits in a V2 frame.
+    pub fn request(cmd: u16) -> MspPacket {
+        MspPacket {
+            cmd,
+            direction: MspPacketDirection::ToFlightController,
+            version: if cmd > 0xff {
+                MspVersion::V2
+            } else {
+                MspVersion::V1
+            },
+            flag: 0,
+            data: vec![],
+        }
+    }
+
+    /// Build a response to `request`, with the same command code, version and flag, directed
+    /// from the flight controller and carrying `data`. Echoing the flag keeps a sequence number
+    /// set with `with_sequence`. Does not check whether the command expects a reply.
+    pub fn reply_to(request: &MspPacket, data: Vec<u8>) -> MspPacket {
+        MspPacket {
+            cmd: request.cmd,
+            direction: MspPacketDirection::FromFlightController,
+            version: request.version,
+            flag: request.flag,
+            data,
+        }
+    }
+
+    /// Replace the payload, for chaining onto a constructor such as `MspPacket::request`
+    pub fn with_payload(mut self, data: Vec<u8>) -> MspPacket {
+        self.data = data;
+        self
+    }
+
+    /// Replace the V2 flag byte. It is not sent in V1 frames.
+    pub fn with_flag(mut self, flag: u8) -> MspPacket {
+        self.flag = flag;
+        self
+    }
+
+    /// Tag the packet with a sequence number, carried in the V2 flag byte. Only firmware that
+    /// echoes the flag in its response can be matched on it, see `same_transaction`. A V1
+    /// `MspClient` tunnels tagged requests in V2 frames, so the flag is sent.
+    pub fn with_sequence(self, sequence: u8) -> MspPacket {
+        self.with_flag(sequence)
+    }
+
+    /// Is `response` the reply to this request? The commands must match, and so must the
+    /// sequence numbers if this packet was tagged with one. A sequence of zero matches any
+    /// response to the command.
+    pub fn same_transaction(&self, response: &MspPacket) -> bool {
+        self.cmd == response.cmd && (self.flag == 0 || self.flag == response.flag)
+    }
+
+    /// Replace the direction
+    pub fn with_direction(mut self, direction: MspPacketDirection) -> MspPacket {
+        self.direction = direction;
+        self
+    }
+
+    /// The command as a `MspCommand`, for matching on in dispatch code
+    pub fn command_enum(&self) -> MspCommand {
+        MspCommand::from(self.cmd)
+    }
+
+    /// Decode the payload as a `T`, failing with `WrongCommand` if the packet was sent with a
+    /// different command
+    pub fn payload_as<T: MspPayload>(&self) -> Result<T, MspPacketParseError> {
+        if self.cmd != T::COMMAND {
+            return Err(MspPacketParseError::WrongCommand {
+                expected: T::COMMAND,
+                actual: self.cmd,
+            });
+        }
+
+        T::decode(&self.data)
+    }
+
+    /// Compare the command and payload, ignoring the direction and the protocol version. Useful
+    /// to match a captured response against the request or reply it mirrors.
+    pub fn eq_ignore_direction(&self, other: &MspPacket) -> bool {
+        self.cmd == other.cmd && self.data == other.data
+    }
+
+    /// Number of payload bytes
+    pub fn payload_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Is the payload empty?
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Consume the packet, returning its payload without copying it
+    pub fn into_payload(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Move the payload out of the packet, leaving it empty
+    pub fn take_payload(&mut self) -> Vec<u8> {
+        mem::take(&mut self.data)
+    }
+
+    /// Is this packet directed to the flight controller?
+    pub fn is_request(&self) -> bool {
+        self.direction == MspPacketDirection::ToFlightController
+    }
+
+    /// Is this packet a reply from the flight controller?
+    pub fn is_response(&self) -> bool {
+        self.direction == MspPacketDirection::FromFlightController
+    }
+
+    /// Is this packet the flight controller reporting an unsupported command?
+    pub fn is_error(&self) -> bool {
+        self.direction == MspPacketDirection::Unsupported
+    }
+
+    /// Can the packet be sent in a V1 frame? V1 frames carry an 8 bit command code and at most
+    /// 255 payload bytes. `serialize` and `write_v1` refuse anything else.
+    pub fn fits_v1(&self) -> bool {
+        self.cmd <= 0xff && self.data.len() <= 0xff
+    }
+
+    /// Wrap the packet's V2 frame in a V1 frame with the `MSP_V2_FRAME_ID` command, for sending
+    /// V2 commands over a V1 link. The parser unwraps it back into a V2 packet. Fails with
+    /// `InvalidDataLength` if the V2 frame does not fit in a V1 payload.
+    pub fn tunnel_v1(&self) -> Result<MspPacket, MspPacketParseError> {
+        let mut frame = vec![0; self.packet_size_bytes_v2()];
+        self.serialize_v2(&mut frame)?;
+        if frame.len() - 3 > 0xff {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        Ok(MspPacket {
+            cmd: MSP_V2_FRAME_ID,
+            direction: self.direction,
+            version: MspVersion::V1,
+            flag: 0,
+            data: frame.split_off(3),
+        })
+    }
+
+    /// The V1 frames needed to carry this packet, for bridging a V2 link to a V1 one. This is
+    /// the packet itself when it fits, and its V2 frame tunneled with `tunnel_v1` otherwise.
+    /// MSP has no fragmentation, so a payload too large for both fails with
+    /// `InvalidDataLength`.
+    pub fn split_for_v1(&self) -> Result<Vec<MspPacket>, MspPacketParseError> {
+        if !self.fits_v1() {
+            return self.tunnel_v1().map(|p| vec![p]);
+        }
+
+        Ok(vec![MspPacket {
+            version: MspVersion::V1,
+            flag: 0,
+            ..self.clone()
+        }])
+    }
+
+    /// Checksum of the packet's V1 frame, as written by `serialize`
+    pub fn checksum_v1(&self) -> u8 {
+        crc_v1(self.data.len() as u8, self.cmd as u8, &self.data)
+    }
+
+    /// Checksum of the packet's V2 frame, as written by `serialize_v2`
+    pub fn checksum_v2(&self) -> u8 {
+        crc_v2(self.flag, self.cmd, &self.data)
+    }
+
     /// Number of bytes that this packet requires to be packed
     pub fn packet_size_bytes(&self) -> usize {
         6 + self.data.len()
@@ -287,6 +1261,12 @@ impl MspPacket {
         if l != self.packet_size_bytes() {
             return Err(MspPacketParseError::OutputBufferSizeMismatch);
         }
+        if self.cmd > 0xff {
+            return Err(MspPacketParseError::InvalidData);
+        }
+        if self.data.len() > 0xff {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
 
         output[0] = b'$';
         output[1] = b'M';
@@ -296,38 +1276,219 @@ impl MspPacket {
 
         output[5..l - 1].copy_from_slice(&self.data);
 
-        let mut crc = output[3] ^ output[4];
-        for b in &*self.data {
-            crc ^= *b;
-        }
-        output[l - 1] = crc;
+        output[l - 1] = self.checksum_v1();
 
         Ok(())
     }
 
     /// Serialize to network bytes
     pub fn serialize_v2(&self, output: &mut [u8]) -> Result<(), MspPacketParseError> {
+        self.serialize_v2_with_crc(output, crc8_dvb_s2_frame)
+    }
+
+    /// Serialize to network bytes, with the checksum calculated by `crc_fn`. See
+    /// `MspParser::with_crc_fn`.
+    pub fn serialize_v2_with_crc(
+        &self,
+        output: &mut [u8],
+        crc_fn: fn(&[u8]) -> u8,
+    ) -> Result<(), MspPacketParseError> {
         let l = output.len();
 
         if l != self.packet_size_bytes_v2() {
             return Err(MspPacketParseError::OutputBufferSizeMismatch);
         }
+        if self.data.len() > u16::MAX as usize {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
 
         output[0] = b'$';
         output[1] = b'X';
         output[2] = self.direction.to_byte();
-        output[3] = 0;
+        output[3] = self.flag;
         output[4..6].copy_from_slice(&self.cmd.to_le_bytes());
         output[6..8].copy_from_slice(&(self.data.len() as u16).to_le_bytes());
 
         output[8..l - 1].copy_from_slice(&self.data);
 
-        let mut crc = CRCu8::crc8dvb_s2();
-        crc.digest(&output[3..l - 1]);
-        output[l - 1] = crc.get_crc();
+        output[l - 1] = crc_fn(&output[3..l - 1]);
 
         Ok(())
     }
+
+    /// Serialize into `buf`, replacing its contents. The buffer's allocation is reused when it is
+    /// large enough, so a sender can keep one scratch buffer for all its packets.
+    pub fn serialize_into(&self, buf: &mut Vec<u8>) -> Result<(), MspPacketParseError> {
+        buf.clear();
+        if self.cmd > 0xff {
+            return Err(MspPacketParseError::InvalidData);
+        }
+        if self.data.len() > 0xff {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        buf.resize(self.packet_size_bytes(), 0);
+        self.serialize(buf)
+    }
+
+    /// Serialize into `buf`, replacing its contents. See `serialize_into`.
+    pub fn serialize_v2_into(&self, buf: &mut Vec<u8>) -> Result<(), MspPacketParseError> {
+        buf.clear();
+        if self.data.len() > u16::MAX as usize {
+            return Err(MspPacketParseError::InvalidDataLength);
+        }
+
+        buf.resize(self.packet_size_bytes_v2(), 0);
+        self.serialize_v2(buf)
+    }
+
+    /// Serialize into a freshly allocated `BytesMut`, ready to be frozen and handed to a sink
+    #[cfg(feature = "bytes")]
+    pub fn serialize_to_bytes(&self) -> Result<::bytes::BytesMut, MspPacketParseError> {
+        let mut output = ::bytes::BytesMut::zeroed(self.packet_size_bytes());
+        self.serialize(&mut output)?;
+        Ok(output)
+    }
+
+    /// Serialize into a freshly allocated `BytesMut`, ready to be frozen and handed to a sink
+    #[cfg(feature = "bytes")]
+    pub fn serialize_v2_to_bytes(&self) -> Result<::bytes::BytesMut, MspPacketParseError> {
+        let mut output = ::bytes::BytesMut::zeroed(self.packet_size_bytes_v2());
+        self.serialize_v2(&mut output)?;
+        Ok(output)
+    }
+
+    /// Serialize into a stack array, returned with the number of bytes used by the frame. Fails
+    /// with `OutputBufferSizeMismatch` if `N` is smaller than `packet_size_bytes`.
+    pub fn serialize_array<const N: usize>(&self) -> Result<([u8; N], usize), MspPacketParseError> {
+        let len = self.packet_size_bytes();
+        if N < len {
+            return Err(MspPacketParseError::OutputBufferSizeMismatch);
+        }
+
+        let mut output = [0; N];
+        self.serialize(&mut output[..len])?;
+        Ok((output, len))
+    }
+
+    /// Serialize into a stack array, returned with the number of bytes used by the frame. Fails
+    /// with `OutputBufferSizeMismatch` if `N` is smaller than `packet_size_bytes_v2`.
+    pub fn serialize_v2_array<const N: usize>(
+        &self,
+    ) -> Result<([u8; N], usize), MspPacketParseError> {
+        let len = self.packet_size_bytes_v2();
+        if N < len {
+            return Err(MspPacketParseError::OutputBufferSizeMismatch);
+        }
+
+        let mut output = [0; N];
+        self.serialize_v2(&mut output[..len])?;
+        Ok((output, len))
+    }
+
+    /// Serialize to network bytes, writing the frame straight into `w`
+    #[cfg(feature = "std")]
+    pub fn write_v1<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if self.cmd > 0xff {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "command does not fit in a V1 frame",
+            ));
+        }
+        if self.data.len() > 0xff {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "payload does not fit in a V1 frame",
+            ));
+        }
+
+        let header = [self.data.len() as u8, self.cmd as u8];
+        w.write_all(&[b'$', b'M', self.direction.to_byte()])?;
+        w.write_all(&header)?;
+        w.write_all(&self.data)?;
+
+        w.write_all(&[self.checksum_v1()])
+    }
+
+    /// Serialize to network bytes, writing the frame straight into `w`
+    #[cfg(feature = "std")]
+    pub fn write_v2<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        if self.data.len() > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "payload does not fit in a V2 frame",
+            ));
+        }
+
+        let cmd = self.cmd.to_le_bytes();
+        let len = (self.data.len() as u16).to_le_bytes();
+        let header = [self.flag, cmd[0], cmd[1], len[0], len[1]];
+        w.write_all(&[b'$', b'X', self.direction.to_byte()])?;
+        w.write_all(&header)?;
+        w.write_all(&self.data)?;
+
+        w.write_all(&[self.checksum_v2()])
+    }
+}
+
+/// One line summary with the direction, version, command, payload length and hex payload,
+/// such as `> v1 cmd=108 len=3 [1a 00 2b]`
+impl Display for MspPacket {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let version = match self.version {
+            MspVersion::V1 => "v1",
+            MspVersion::V2 => "v2",
+        };
+        write!(
+            f,
+            "{} {} cmd={} len={} [",
+            self.direction.to_byte() as char,
+            version,
+            self.cmd,
+            self.data.len()
+        )?;
+        for (i, b) in self.data.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// Serializes the packet as a V2 frame, regardless of its `version`
+impl From<MspPacket> for Vec<u8> {
+    fn from(packet: MspPacket) -> Vec<u8> {
+        let mut output = vec![0; packet.packet_size_bytes_v2()];
+        packet
+            .serialize_v2(&mut output)
+            .expect("output is sized for the packet");
+        output
+    }
+}
+
+/// Same as `MspPacket::request`
+impl From<u16> for MspPacket {
+    fn from(cmd: u16) -> MspPacket {
+        MspPacket::request(cmd)
+    }
+}
+
+/// A request to the flight controller with the given command and payload
+impl From<(u16, Vec<u8>)> for MspPacket {
+    fn from((cmd, data): (u16, Vec<u8>)) -> MspPacket {
+        MspPacket::request(cmd).with_payload(data)
+    }
+}
+
+/// Same as `MspPacket::parse_one`: exactly one complete V1 or V2 frame, with no trailing bytes
+impl<'a> TryFrom<&'a [u8]> for MspPacket {
+    type Error = MspPacketParseError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<MspPacket, MspPacketParseError> {
+        MspPacket::parse_one(bytes)
+    }
 }
 
 #[test]
@@ -335,6 +1496,8 @@ fn test_serialize() {
     let packet = MspPacket {
         cmd: 2,
         direction: MspPacketDirection::ToFlightController,
+        version: MspVersion::V1,
+        flag: 0,
         data: vec![0xbe, 0xef],
     };
 
@@ -364,10 +1527,18 @@ fn test_serialize() {
 #[test]
 fn test_roundtrip() {
     fn roundtrip(packet: &MspPacket) {
-        let size = packet.packet_size_bytes();
-        let mut output = vec![0; size];
-
-        packet.serialize(&mut output).unwrap();
+        let output = match packet.version {
+            MspVersion::V1 => {
+                let mut output = vec![0; packet.packet_size_bytes()];
+                packet.serialize(&mut output).unwrap();
+                output
+            }
+            MspVersion::V2 => {
+                let mut output = vec![0; packet.packet_size_bytes_v2()];
+                packet.serialize_v2(&mut output).unwrap();
+                output
+            }
+        };
 
         let mut parser = MspParser::new();
         let mut packet_parsed = None;
@@ -384,6 +1555,8 @@ fn test_roundtrip() {
         let packet = MspPacket {
             cmd: 1,
             direction: MspPacketDirection::ToFlightController,
+            version: MspVersion::V1,
+            flag: 0,
             data: vec![0x00, 0x00, 0x00],
         };
         roundtrip(&packet);
@@ -393,6 +1566,8 @@ fn test_roundtrip() {
         let packet = MspPacket {
             cmd: 200,
             direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V1,
+            flag: 0,
             data: vec![],
         };
         roundtrip(&packet);
@@ -402,8 +1577,1424 @@ fn test_roundtrip() {
         let packet = MspPacket {
             cmd: 100,
             direction: MspPacketDirection::Unsupported,
+            version: MspVersion::V1,
+            flag: 0,
             data: vec![0x44, 0x20, 0x00, 0x80],
         };
         roundtrip(&packet);
     }
+
+    {
+        let packet = MspPacket {
+            cmd: 0x2012,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: vec![0x44, 0x20, 0x00, 0x80],
+        };
+        roundtrip(&packet);
+    }
+}
+
+#[test]
+fn test_parse_into() {
+    let packets = [
+        MspPacket {
+            cmd: 1,
+            direction: MspPacketDirection::ToFlightController,
+            version: MspVersion::V1,
+            flag: 0,
+            data: vec![0x00, 0x00, 0x00],
+        },
+        MspPacket {
+            cmd: 0x2012,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: vec![0x44, 0x20, 0x00, 0x80],
+        },
+    ];
+
+    let mut parser = MspParser::new();
+    let mut packet_parsed = None;
+    for packet in &packets {
+        let output = match packet.version {
+            MspVersion::V1 => {
+                let mut output = vec![0; packet.packet_size_bytes()];
+                packet.serialize(&mut output).unwrap();
+                output
+            }
+            MspVersion::V2 => {
+                let mut output = vec![0; packet.packet_size_bytes_v2()];
+                packet.serialize_v2(&mut output).unwrap();
+                output
+            }
+        };
+        let mut produced = 0;
+        for b in output {
+            if parser.parse_into(b, &mut packet_parsed).unwrap() {
+                produced += 1;
+            }
+        }
+        assert_eq!(1, produced);
+        assert_eq!(Some(packet), packet_parsed.as_ref());
+    }
+
+    // a byte that does not complete a packet leaves the slot untouched
+    assert_eq!(Ok(false), parser.parse_into(b'$', &mut packet_parsed));
+    assert_eq!(Some(&packets[1]), packet_parsed.as_ref());
+}
+
+#[test]
+fn test_data_length_underflow() {
+    for state in &[MspParserState::Data, MspParserState::DataV2] {
+        let mut parser = MspParser::new();
+        parser.state = *state;
+        parser.packet_data_length_remaining = 0;
+
+        assert_eq!(Err(MspPacketParseError::InvalidData), parser.parse(0x00));
+        assert!(parser.state_is_between_packets());
+    }
+}
+
+#[test]
+fn test_feed() {
+    let packet = MspPacket {
+        cmd: 101,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V1,
+        flag: 0,
+        data: vec![0x01, 0x02],
+    };
+    let mut frame = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut frame).unwrap();
+
+    let mut input = frame.repeat(3);
+    let mut parser = MspParser::new();
+    let mut count = 0;
+    parser.feed(&input, |_| count += 1).unwrap();
+    assert_eq!(3, count);
+
+    // break the header of the third frame
+    input[frame.len() * 2 + 1] = b'?';
+    let mut count = 0;
+    assert_eq!(
+        Err(MspPacketParseError::InvalidHeader2),
+        parser.feed(&input, |p| {
+            assert_eq!(packet, p);
+            count += 1
+        })
+    );
+    assert_eq!(2, count);
+}
+
+#[test]
+fn test_crc_error_with_embedded_frame() {
+    // the payload is itself a valid MSP_STATUS request
+    let mut inner = vec![0; MspPacket::request(101).packet_size_bytes()];
+    MspPacket::request(101).serialize(&mut inner).unwrap();
+    let outer = MspPacket::request(200).with_payload(inner);
+    let mut input = vec![0; outer.packet_size_bytes()];
+    outer.serialize(&mut input).unwrap();
+    *input.last_mut().unwrap() ^= 0xff;
+
+    // a CRC mismatch is only found once the declared length has been consumed, so the
+    // embedded frame is never mistaken for a packet
+    let mut parser = MspParser::new();
+    let parsed: Vec<_> = parser.parse_iter(&input).collect();
+    assert_eq!(1, parsed.len());
+    assert!(matches!(
+        parsed[0],
+        Err(MspPacketParseError::CrcMismatch { .. })
+    ));
+    assert!(parser.state_is_between_packets());
+}
+
+#[test]
+fn test_parse_until_error() {
+    let frame = |cmd: u16| {
+        let packet = MspPacket::request(cmd).with_payload(vec![0x01, 0x02]);
+        let mut output = vec![0; packet.packet_size_bytes()];
+        packet.serialize(&mut output).unwrap();
+        output
+    };
+    let mut bad = frame(102);
+    *bad.last_mut().unwrap() ^= 0xff;
+    let input = [frame(101), bad.clone(), frame(103)].concat();
+
+    let mut parser = MspParser::new();
+    let (packets, consumed, error) = parser.parse_until_error(&input);
+    assert_eq!(vec![101], packets.iter().map(|p| p.cmd).collect::<Vec<_>>());
+    assert_eq!(frame(101).len() + bad.len(), consumed);
+    assert!(matches!(
+        error,
+        Some(MspPacketParseError::CrcMismatch { .. })
+    ));
+
+    let (packets, rest, error) = parser.parse_until_error(&input[consumed..]);
+    assert_eq!(vec![103], packets.iter().map(|p| p.cmd).collect::<Vec<_>>());
+    assert_eq!(input.len(), consumed + rest);
+    assert_eq!(None, error);
+}
+
+#[test]
+fn test_fits_v1() {
+    let packet = MspPacket::request(0x10).with_payload(vec![0; 300]);
+    assert!(!packet.fits_v1());
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        packet.split_for_v1()
+    );
+    let mut output = vec![0; packet.packet_size_bytes()];
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        packet.serialize(&mut output)
+    );
+    #[cfg(feature = "std")]
+    assert!(packet.write_v1(&mut vec![]).is_err());
+
+    let packet = MspPacket::request(0x1003).with_payload(vec![1, 2, 3]);
+    assert!(!packet.fits_v1());
+    let mut output = vec![0; packet.packet_size_bytes()];
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        packet.serialize(&mut output)
+    );
+    let mut output = vec![0xff];
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        packet.serialize_into(&mut output)
+    );
+    assert!(output.is_empty());
+    #[cfg(feature = "std")]
+    assert!(packet.write_v1(&mut vec![]).is_err());
+
+    // commands above 255 are tunneled as a V2 frame in a V1 payload
+    let split = packet.split_for_v1().unwrap();
+    assert_eq!(1, split.len());
+    assert_eq!(MspVersion::V1, split[0].version);
+    assert_eq!(255, split[0].cmd);
+    split[0].serialize_into(&mut output).unwrap();
+    assert_eq!(Ok(packet.clone()), MspPacket::parse_one(&output));
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        packet.with_payload(vec![0; 250]).split_for_v1()
+    );
+    assert!(MspPacket::request(0x1003)
+        .with_payload(vec![0; 249])
+        .split_for_v1()
+        .is_ok());
+
+    let mut packet = MspPacket::request(0x10).with_payload(vec![0; 255]);
+    packet.version = MspVersion::V2;
+    assert!(packet.fits_v1());
+    let split = packet.split_for_v1().unwrap();
+    assert_eq!(1, split.len());
+    assert_eq!(MspVersion::V1, split[0].version);
+    assert_eq!(packet.data, split[0].data);
+}
+
+#[test]
+fn test_v2_oversized() {
+    let packet = MspPacket::request(0x1003).with_payload(vec![0; u16::MAX as usize + 1]);
+    let mut output = vec![0; packet.packet_size_bytes_v2()];
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        packet.serialize_v2(&mut output)
+    );
+    let mut output = vec![0xff];
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        packet.serialize_v2_into(&mut output)
+    );
+    assert!(output.is_empty());
+    #[cfg(feature = "std")]
+    assert_eq!(
+        io::ErrorKind::InvalidInput,
+        packet.write_v2(&mut vec![]).unwrap_err().kind()
+    );
+
+    let packet = packet.with_payload(vec![0; u16::MAX as usize]);
+    packet.serialize_v2_into(&mut output).unwrap();
+    assert_eq!(Ok(packet.clone()), MspPacket::parse_one(&output));
+    #[cfg(feature = "std")]
+    {
+        let mut written = vec![];
+        packet.write_v2(&mut written).unwrap();
+        assert_eq!(output, written);
+    }
+}
+
+#[test]
+fn test_stats() {
+    let packet = MspPacket::request(101);
+    let mut frame = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut frame).unwrap();
+
+    let mut input = frame.repeat(3);
+    // corrupt the checksum of the second frame and the header of the third
+    input[frame.len() * 2 - 1] ^= 0xff;
+    input[frame.len() * 2 + 1] = b'?';
+    input.extend_from_slice(&frame);
+
+    let mut parser = MspParser::new();
+    for b in &input {
+        let _ = parser.parse(*b);
+    }
+    assert_eq!(
+        MspParserStats {
+            packets: 2,
+            crc_errors: 1,
+            framing_errors: 1,
+        },
+        parser.stats()
+    );
+
+    parser.reset();
+    assert_eq!(2, parser.stats().packets);
+    parser.hard_reset();
+    assert_eq!(MspParserStats::default(), parser.stats());
+}
+
+#[test]
+fn test_custom_crc() {
+    fn xor_crc(data: &[u8]) -> u8 {
+        data.iter().fold(0x5a, |crc, b| crc ^ b)
+    }
+
+    let packet = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x01, 0x02, 0x03],
+    };
+    let mut frame = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2_with_crc(&mut frame, xor_crc).unwrap();
+    assert_eq!(xor_crc(&frame[3..frame.len() - 1]), frame[frame.len() - 1]);
+
+    let mut parser = MspParser::new().with_crc_fn(xor_crc);
+    parser.feed(&frame[..frame.len() - 1], |_| {}).unwrap();
+    assert_eq!(0, parser.packet_crc_v2, "the built-in CRC is not run");
+    let mut parsed = vec![];
+    parser
+        .feed(&frame[frame.len() - 1..], |p| parsed.push(p))
+        .unwrap();
+    assert_eq!(vec![packet.clone()], parsed);
+
+    // the default checksum does not match anymore
+    assert!(matches!(
+        MspPacket::parse_one(&frame),
+        Err(MspPacketParseError::CrcMismatch { .. })
+    ));
+
+    // and the custom one applies to V2 frames tunneled in V1 as well
+    let tunneled = MspPacket {
+        cmd: 255,
+        version: MspVersion::V1,
+        flag: 0,
+        data: frame[3..].to_vec(),
+        ..packet.clone()
+    };
+    let mut outer = vec![0; tunneled.packet_size_bytes()];
+    tunneled.serialize(&mut outer).unwrap();
+    let mut parsed = vec![];
+    parser.feed(&outer, |p| parsed.push(p)).unwrap();
+    assert_eq!(vec![packet], parsed);
+}
+
+#[test]
+fn test_v2_crc_across_chunks() {
+    for len in &[0, 1, 7, 64, 300] {
+        let packet = MspPacket {
+            cmd: 0x2000,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: (0..*len).map(|i| (i * 7) as u8).collect(),
+        };
+        let frame = Vec::from(packet.clone());
+        assert_eq!(crc_v2(0, 0x2000, &packet.data), frame[frame.len() - 1]);
+
+        for chunk_size in &[1, 3, frame.len()] {
+            let mut parser = MspParser::new();
+            let mut parsed = vec![];
+            for chunk in frame.chunks(*chunk_size) {
+                parser.feed(chunk, |p| parsed.push(p)).unwrap();
+            }
+            assert_eq!(vec![packet.clone()], parsed);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_parse_reader() {
+    let first = MspPacket::request(101);
+    let second = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x2a; 100],
+    };
+    let mut input = vec![];
+    first.write_v1(&mut input).unwrap();
+    second.write_v2(&mut input).unwrap();
+
+    let mut reader = io::Cursor::new(input);
+    let mut parser = MspParser::new();
+    assert_eq!(Ok(first), parser.parse_reader(&mut reader));
+    assert_eq!(Ok(second), parser.parse_reader(&mut reader));
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::UnexpectedEof)),
+        parser.parse_reader(&mut reader)
+    );
+
+    // both frames arrive in the first read; a reset drops the second one
+    reader.set_position(0);
+    let mut parser = MspParser::new();
+    assert!(parser.parse_reader(&mut reader).is_ok());
+    parser.reset();
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::UnexpectedEof)),
+        parser.parse_reader(&mut reader)
+    );
+
+    reader.set_position(0);
+    let mut parser = MspParser::new();
+    assert!(parser.parse_reader(&mut reader).is_ok());
+    parser.hard_reset();
+    assert_eq!(
+        Err(MspPacketParseError::Io(io::ErrorKind::UnexpectedEof)),
+        parser.parse_reader(&mut reader)
+    );
+}
+
+#[test]
+fn test_parse_iter() {
+    let packets: Vec<_> = (0..4)
+        .map(|i| MspPacket {
+            cmd: 100 + i,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: vec![i as u8; 5],
+        })
+        .collect();
+    let mut input = vec![];
+    for packet in &packets {
+        input.extend(Vec::from(packet.clone()));
+    }
+    // break the checksum of the last packet
+    let last = input.len() - 1;
+    input[last] ^= 0xff;
+
+    // the second packet spans both chunks
+    let (first, second) = input.split_at(20);
+    let mut parser = MspParser::new();
+    let mut parsed: Vec<_> = parser.parse_iter(first).collect();
+    assert!(!parser.state_is_between_packets());
+    parsed.extend(parser.parse_iter(second));
+
+    assert_eq!(4, parsed.len());
+    for (packet, result) in packets.iter().zip(&parsed[..3]) {
+        assert_eq!(&Ok(packet.clone()), result);
+    }
+    assert!(matches!(
+        parsed[3],
+        Err(MspPacketParseError::CrcMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_clone_mid_packet() {
+    let a = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x01, 0x02, 0x03, 0x04],
+    };
+    let b = MspPacket {
+        data: vec![0x01, 0x02, 0x05, 0x06],
+        ..a.clone()
+    };
+    let mut frame_a = vec![0; a.packet_size_bytes_v2()];
+    a.serialize_v2(&mut frame_a).unwrap();
+    let mut frame_b = vec![0; b.packet_size_bytes_v2()];
+    b.serialize_v2(&mut frame_b).unwrap();
+
+    // both frames share everything up to the third payload byte
+    let split = 10;
+    let mut parser = MspParser::new();
+    for byte in &frame_a[..split] {
+        assert_eq!(None, parser.parse(*byte).unwrap());
+    }
+    let mut fork = parser.clone();
+
+    let mut parsed_a = None;
+    for byte in &frame_a[split..] {
+        parsed_a = parser.parse(*byte).unwrap();
+    }
+    let mut parsed_b = None;
+    for byte in &frame_b[split..] {
+        parsed_b = fork.parse(*byte).unwrap();
+    }
+    assert_eq!(Some(a), parsed_a);
+    assert_eq!(Some(b), parsed_b);
+}
+
+#[test]
+fn test_crc() {
+    assert_eq!(81, crc_v1(2, 2, &[0xbe, 0xef]));
+    assert_eq!(0x3f, crc_v2(0, 0x1003, &[0xab, 0xcd]));
+
+    let packet = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0xab, 0xcd],
+    };
+    let mut output = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut output).unwrap();
+    assert_eq!(0x3f, output[output.len() - 1]);
+}
+
+#[test]
+fn test_checksum() {
+    let packet = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x01, 0x02, 0x03],
+    };
+
+    let v1_packet = MspPacket {
+        cmd: 0x03,
+        ..packet.clone()
+    };
+    let mut v1 = vec![0; v1_packet.packet_size_bytes()];
+    v1_packet.serialize(&mut v1).unwrap();
+    assert_eq!(v1[v1.len() - 1], v1_packet.checksum_v1());
+
+    let mut v2 = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut v2).unwrap();
+    assert_eq!(v2[v2.len() - 1], packet.checksum_v2());
+}
+
+#[test]
+fn test_unsupported_roundtrip() {
+    let packet = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::Unsupported,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![],
+    };
+    let mut output = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut output).unwrap();
+    assert_eq!(b'!', output[2]);
+    assert_eq!(Ok(packet.clone()), MspPacket::parse_one(&output));
+
+    let packet = MspPacket {
+        cmd: 101,
+        version: MspVersion::V1,
+        flag: 0,
+        ..packet
+    };
+    let mut output = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut output).unwrap();
+    assert_eq!(b'!', output[2]);
+    assert_eq!(Ok(packet), MspPacket::parse_one(&output));
+}
+
+#[test]
+fn test_serialize_array() {
+    let packet = MspPacket::request(0x1003).with_payload(vec![0x01, 0x02, 0x03]);
+
+    let (output, len) = packet.serialize_v2_array::<32>().unwrap();
+    assert_eq!(12, len);
+    assert_eq!(Vec::from(packet.clone()), output[..len].to_vec());
+    assert!(output[len..].iter().all(|b| *b == 0));
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidData),
+        packet.serialize_array::<32>()
+    );
+    let packet = MspPacket::request(0x03).with_payload(vec![0x01, 0x02, 0x03]);
+    let (output, len) = packet.serialize_array::<32>().unwrap();
+    let mut expected = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut expected).unwrap();
+    assert_eq!(expected, output[..len].to_vec());
+
+    assert_eq!(
+        Err(MspPacketParseError::OutputBufferSizeMismatch),
+        packet.serialize_v2_array::<11>()
+    );
+}
+
+#[test]
+fn test_into_vec() {
+    let packet = MspPacket {
+        cmd: 101,
+        direction: MspPacketDirection::ToFlightController,
+        version: MspVersion::V1,
+        flag: 0,
+        data: vec![0x01],
+    };
+
+    let mut expected = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut expected).unwrap();
+    let output: Vec<u8> = packet.into();
+    assert_eq!(expected, output);
+}
+
+#[test]
+fn test_try_from_slice() {
+    let packet = MspPacket {
+        cmd: 101,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V1,
+        flag: 0,
+        data: vec![0x01, 0x02],
+    };
+    let mut v1 = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut v1).unwrap();
+    assert_eq!(Ok(packet.clone()), MspPacket::try_from(&v1[..]));
+
+    let packet = MspPacket {
+        version: MspVersion::V2,
+        flag: 0,
+        ..packet
+    };
+    let mut v2 = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut v2).unwrap();
+    assert_eq!(Ok(packet), MspPacket::try_from(&v2[..]));
+
+    v2.push(b'$');
+    assert_eq!(
+        Err(MspPacketParseError::TrailingData),
+        MspPacket::try_from(&v2[..])
+    );
+}
+
+#[test]
+fn test_expected_version() {
+    let packet = MspPacket::request(101);
+    let mut v1 = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut v1).unwrap();
+    let mut v2 = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut v2).unwrap();
+
+    let mut parser = MspParser::new().with_expected_version(MspVersion::V2);
+    assert_eq!(Ok(None), parser.parse(v1[0]));
+    assert_eq!(
+        Err(MspPacketParseError::UnexpectedVersion),
+        parser.parse(v1[1])
+    );
+
+    let mut parsed = vec![];
+    parser.feed(&v2, |p| parsed.push(p)).unwrap();
+    assert_eq!(1, parsed.len());
+    assert_eq!(MspVersion::V2, parsed[0].version);
+
+    let mut parser = MspParser::new();
+    let mut parsed = 0;
+    parser.feed(&v1, |_| parsed += 1).unwrap();
+    parser.feed(&v2, |_| parsed += 1).unwrap();
+    assert_eq!(2, parsed);
+}
+
+#[test]
+fn test_allowed_commands() {
+    let mut input = vec![];
+    for cmd in &[101, 108, 102] {
+        let packet = MspPacket::request(*cmd);
+        let mut output = vec![0; packet.packet_size_bytes()];
+        packet.serialize(&mut output).unwrap();
+        input.extend_from_slice(&output);
+    }
+
+    let mut parser = MspParser::new().with_allowed_commands(&[101, 102]);
+    let parsed: Vec<_> = parser.parse_iter(&input).collect();
+    assert_eq!(
+        vec![
+            Ok(MspPacket::request(101)),
+            Err(MspPacketParseError::UnexpectedCommand { cmd: 108 }),
+            Ok(MspPacket::request(102)),
+        ],
+        parsed
+    );
+    assert!(parser.state_is_between_packets());
+
+    let mut parser = MspParser::new();
+    assert_eq!(3, parser.parse_iter(&input).filter(|r| r.is_ok()).count());
+}
+
+#[test]
+fn test_new_v1_v2() {
+    let mut parser = MspParser::new_v2();
+    assert_eq!(Ok(None), parser.parse(b'$'));
+    assert_eq!(
+        Err(MspPacketParseError::UnexpectedVersion),
+        parser.parse(b'M')
+    );
+
+    let mut parser = MspParser::new_v1();
+    assert_eq!(Ok(None), parser.parse(b'$'));
+    assert_eq!(
+        Err(MspPacketParseError::UnexpectedVersion),
+        parser.parse(b'X')
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_io_error() {
+    let e = MspPacketParseError::from(io::Error::new(io::ErrorKind::BrokenPipe, "unplugged"));
+    assert!(matches!(
+        e,
+        MspPacketParseError::Io(io::ErrorKind::BrokenPipe)
+    ));
+    assert_eq!(MspPacketParseError::Io(io::ErrorKind::BrokenPipe), e);
+}
+
+#[test]
+fn test_parse_one() {
+    let packet = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x01, 0x02, 0x03],
+    };
+
+    let mut output = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut output).unwrap();
+    assert_eq!(packet, MspPacket::parse_one(&output).unwrap());
+
+    assert_eq!(
+        Err(MspPacketParseError::IncompletePacket),
+        MspPacket::parse_one(&output[..output.len() - 1])
+    );
+
+    output.push(0xff);
+    assert_eq!(
+        Err(MspPacketParseError::TrailingData),
+        MspPacket::parse_one(&output)
+    );
+}
+
+#[test]
+fn test_validate_frame() {
+    let packet = MspPacket {
+        cmd: 0x1003,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x01, 0x02, 0x03],
+    };
+    let mut output = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut output).unwrap();
+    assert_eq!(Ok(()), MspPacket::validate_frame(&output));
+
+    let v1_packet = MspPacket {
+        cmd: 0x03,
+        ..packet.clone()
+    };
+    let mut v1 = vec![0; v1_packet.packet_size_bytes()];
+    v1_packet.serialize(&mut v1).unwrap();
+    assert_eq!(Ok(()), MspPacket::validate_frame(&v1));
+
+    let crc = output[output.len() - 1];
+    let last = output.len() - 1;
+    output[last] ^= 0xff;
+    assert_eq!(
+        Err(MspPacketParseError::CrcMismatch {
+            expected: crc ^ 0xff,
+            calculated: crc,
+        }),
+        MspPacket::validate_frame(&output)
+    );
+
+    output[0] = b'#';
+    assert_eq!(
+        Err(MspPacketParseError::InvalidHeader1),
+        MspPacket::validate_frame(&output)
+    );
+    assert_eq!(
+        Err(MspPacketParseError::IncompletePacket),
+        MspPacket::validate_frame(&v1[..v1.len() - 1])
+    );
+    v1.push(0);
+    assert_eq!(
+        Err(MspPacketParseError::TrailingData),
+        MspPacket::validate_frame(&v1)
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_write() {
+    let packet = MspPacket {
+        cmd: 0x1009,
+        direction: MspPacketDirection::ToFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x24, 0x4d, 0x3c, 0x00],
+    };
+
+    let v1_packet = MspPacket {
+        cmd: 0x09,
+        ..packet.clone()
+    };
+    let mut expected = vec![0; v1_packet.packet_size_bytes()];
+    v1_packet.serialize(&mut expected).unwrap();
+    let mut written = vec![];
+    v1_packet.write_v1(&mut written).unwrap();
+    assert_eq!(expected, written);
+
+    let mut expected = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut expected).unwrap();
+    let mut written = vec![];
+    packet.write_v2(&mut written).unwrap();
+    assert_eq!(expected, written);
+    assert_eq!(packet, MspPacket::parse_one(&written).unwrap());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_pooled_buffers() {
+    use alloc_counter::count_allocations;
+
+    let packet = MspPacket {
+        cmd: 102,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x42; 18],
+    };
+    let mut frame = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut frame).unwrap();
+
+    fn parse_frames(parser: &mut MspParser, frame: &[u8], recycle: bool) -> usize {
+        let mut parsed = 0;
+        for _ in 0..100 {
+            for b in frame {
+                if let Some(p) = parser.parse(*b).unwrap() {
+                    parsed += 1;
+                    if recycle {
+                        parser.recycle(p.data);
+                    }
+                }
+            }
+        }
+        parsed
+    }
+
+    let mut parser = MspParser::new();
+    let (allocations, parsed) = count_allocations(|| parse_frames(&mut parser, &frame, true));
+    assert_eq!(100, parsed);
+    assert!(allocations >= 100);
+
+    let mut parser = MspParser::new().with_pooled_buffers(2);
+    let (allocations, parsed) = count_allocations(|| parse_frames(&mut parser, &frame, true));
+    assert_eq!(100, parsed);
+    assert!(allocations <= 2, "{} allocations", allocations);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_capacity_hint() {
+    use alloc_counter::count_allocations;
+
+    let packet = MspPacket {
+        cmd: 102,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![0x42; 18],
+    };
+    let mut frame = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut frame).unwrap();
+    let stream = frame.repeat(100);
+
+    // a polling loop handing every payload back once it is done with it
+    let poll = |parser: &mut MspParser| {
+        count_allocations(|| {
+            let mut parsed = 0;
+            for b in &stream {
+                if let Some(p) = parser.parse(*b).unwrap() {
+                    assert_eq!(packet, p);
+                    parsed += 1;
+                    parser.recycle(p.data);
+                }
+            }
+            parsed
+        })
+    };
+
+    let mut parser = MspParser::new();
+    let (unhinted, parsed) = poll(&mut parser);
+    assert_eq!(100, parsed);
+    assert!(unhinted >= 100, "{} allocations", unhinted);
+
+    let mut parser = MspParser::new().with_capacity_hint(18);
+    for b in &frame {
+        if let Some(p) = parser.parse(*b).unwrap() {
+            parser.recycle(p.data);
+        }
+    }
+    let (hinted, parsed) = poll(&mut parser);
+    assert_eq!(100, parsed);
+    assert_eq!(0, hinted);
+
+    // payloads of another length are still parsed, and the hinted buffer survives them; only
+    // the header scratch buffer they took along is allocated again
+    let other = packet.clone().with_payload(vec![0x42; 20]);
+    let mut other_frame = vec![0; other.packet_size_bytes_v2()];
+    other.serialize_v2(&mut other_frame).unwrap();
+    parser.feed(&other_frame, |p| assert_eq!(other, p)).unwrap();
+    let (allocations, parsed) = poll(&mut parser);
+    assert_eq!(100, parsed);
+    assert!(allocations <= 1, "{} allocations", allocations);
+}
+
+#[test]
+fn test_request() {
+    let packet = MspPacket::request(101);
+    assert_eq!(MspPacketDirection::ToFlightController, packet.direction);
+    assert_eq!(MspVersion::V1, packet.version);
+    assert!(packet.data.is_empty());
+
+    let mut output = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut output).unwrap();
+    assert_eq!(&[b'$', b'M', b'<', 0, 101, 101], output.as_slice());
+
+    assert_eq!(MspVersion::V2, MspPacket::request(0x1003).version);
+}
+
+#[test]
+fn test_with_payload() {
+    let packet = MspPacket::request(200)
+        .with_payload(vec![0xdc, 0x05, 0xdc, 0x05])
+        .with_direction(MspPacketDirection::FromFlightController);
+    assert_eq!(
+        MspPacket {
+            cmd: 200,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V1,
+            flag: 0,
+            data: vec![0xdc, 0x05, 0xdc, 0x05],
+        },
+        packet
+    );
+}
+
+#[test]
+fn test_payload_as() {
+    use messages::{MspApiVersion, MspFcVersion};
+
+    let packet = MspPacket::reply_to(&MspPacket::request(1), vec![0, 1, 44]);
+    assert_eq!(
+        Ok(MspApiVersion {
+            protocol: 0,
+            major: 1,
+            minor: 44,
+        }),
+        packet.payload_as()
+    );
+    assert_eq!(
+        Err(MspPacketParseError::WrongCommand {
+            expected: 3,
+            actual: 1,
+        }),
+        packet.payload_as::<MspFcVersion>()
+    );
+}
+
+#[test]
+fn test_eq_ignore_direction() {
+    let request = MspPacket::request(214).with_payload(vec![0xe8, 0x03]);
+    let echo = MspPacket::reply_to(&request, request.data.clone());
+    assert_ne!(request, echo);
+    assert!(request.eq_ignore_direction(&echo));
+
+    let other = MspPacket::reply_to(&request, vec![0xe8, 0x04]);
+    assert!(!request.eq_ignore_direction(&other));
+    assert!(!request.eq_ignore_direction(&MspPacket::request(215).with_payload(vec![0xe8, 0x03])));
+
+    let echo_v2 = MspPacket {
+        version: MspVersion::V2,
+        ..echo
+    };
+    assert!(request.eq_ignore_direction(&echo_v2));
+}
+
+#[test]
+fn test_direction_predicates() {
+    let request = MspPacket::request(101);
+    assert!(request.is_request() && !request.is_response() && !request.is_error());
+
+    let response = MspPacket::reply_to(&request, vec![]);
+    assert!(!response.is_request() && response.is_response() && !response.is_error());
+
+    let error = MspPacket {
+        direction: MspPacketDirection::Unsupported,
+        ..response
+    };
+    assert!(!error.is_request() && !error.is_response() && error.is_error());
+}
+
+#[test]
+fn test_display() {
+    let packet = MspPacket {
+        cmd: 108,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V1,
+        flag: 0,
+        data: vec![0x1a, 0x00, 0x2b],
+    };
+    assert_eq!("> v1 cmd=108 len=3 [1a 00 2b]", format!("{}", packet));
+    assert_eq!(
+        "< v1 cmd=101 len=0 []",
+        format!("{}", MspPacket::request(101))
+    );
+}
+
+#[test]
+fn test_reply_to() {
+    let request = MspPacket {
+        cmd: 108,
+        direction: MspPacketDirection::ToFlightController,
+        version: MspVersion::V2,
+        flag: 0,
+        data: vec![],
+    };
+
+    let reply = MspPacket::reply_to(&request, vec![0x01, 0x02]);
+    assert_eq!(
+        MspPacket {
+            cmd: 108,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: vec![0x01, 0x02],
+        },
+        reply
+    );
+}
+
+#[test]
+fn test_v2_encapsulated_in_v1() {
+    // MSP2_COMMON_SETTING response with payload [0xab, 0xcd], tunneled through V1 command 255
+    let frame = [
+        b'$', b'M', b'>', 8, 255, 0x00, 0x03, 0x10, 0x02, 0x00, 0xab, 0xcd, 0x3f, 0xbf,
+    ];
+
+    let packet = MspPacket::parse_one(&frame).unwrap();
+    assert_eq!(
+        MspPacket {
+            cmd: 0x1003,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: vec![0xab, 0xcd],
+        },
+        packet
+    );
+
+    // inner CRC corrupted, outer V1 CRC fixed up to match
+    let mut corrupted = frame;
+    corrupted[12] ^= 0xff;
+    corrupted[13] ^= 0xff;
+    assert_eq!(
+        Err(MspPacketParseError::CrcMismatch {
+            expected: 0xc0,
+            calculated: 0x3f,
+        }),
+        MspPacket::parse_one(&corrupted)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_json_roundtrip() {
+    let packet = MspPacket {
+        cmd: 108,
+        direction: MspPacketDirection::FromFlightController,
+        version: MspVersion::V1,
+        flag: 0,
+        data: vec![0x1a, 0x00, 0x2b],
+    };
+
+    let json = ::serde_json::to_string(&packet).unwrap();
+    assert_eq!(
+        r#"{"cmd":108,"direction":">","version":"V1","data":[26,0,43]}"#,
+        json.as_str()
+    );
+    assert_eq!(packet, ::serde_json::from_str::<MspPacket>(&json).unwrap());
+
+    let direction: MspPacketDirection = ::serde_json::from_str(r#""!""#).unwrap();
+    assert_eq!(MspPacketDirection::Unsupported, direction);
+    assert!(::serde_json::from_str::<MspPacketDirection>(r#""?""#).is_err());
+
+    let err = MspPacketParseError::CrcMismatch {
+        expected: 1,
+        calculated: 2,
+    };
+    let json = ::serde_json::to_string(&err).unwrap();
+    assert_eq!(err, ::serde_json::from_str(&json).unwrap());
+}
+
+#[test]
+fn test_drain_partial() {
+    let packet = MspPacket::request(108).with_payload(vec![1, 2, 3, 4]);
+    let mut v1 = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut v1).unwrap();
+    let mut v2 = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut v2).unwrap();
+
+    let mut parser = MspParser::new();
+    assert_eq!(0, parser.drain_partial());
+
+    for frame in &[v1, v2] {
+        // every prefix short of the whole frame is reported in full
+        for len in 1..frame.len() {
+            for b in &frame[..len] {
+                assert_eq!(Ok(None), parser.parse(*b));
+            }
+            assert_eq!(len, parser.drain_partial());
+            assert!(parser.state_is_between_packets());
+        }
+
+        let packets: Vec<_> = parser.parse_iter(frame).collect();
+        assert_eq!(1, packets.len());
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_serialize_into() {
+    use alloc_counter::count_allocations;
+
+    let packet = MspPacket::request(102).with_payload(vec![0x42; 18]);
+    let mut v1 = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut v1).unwrap();
+    let mut v2 = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut v2).unwrap();
+
+    let mut buf = vec![0xff; 3];
+    packet.serialize_into(&mut buf).unwrap();
+    assert_eq!(v1, buf);
+    packet.serialize_v2_into(&mut buf).unwrap();
+    assert_eq!(v2, buf);
+
+    // the scratch buffer is large enough after the first packet
+    let (allocations, _) = count_allocations(|| {
+        for _ in 0..100 {
+            packet.serialize_into(&mut buf).unwrap();
+            packet.serialize_v2_into(&mut buf).unwrap();
+        }
+    });
+    assert_eq!(0, allocations);
+
+    let oversized = MspPacket::request(102).with_payload(vec![0; 256]);
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        oversized.serialize_into(&mut buf)
+    );
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_v2_flag() {
+    // zero length V2 command frame with a non-zero flag
+    let frame = [b'$', b'X', b'<', 0x01, 0x0a, 0x10, 0x00, 0x00, 0x72];
+    let packet = MspPacket::parse_one(&frame).unwrap();
+    assert_eq!(0x01, packet.flag);
+    assert_eq!(0x100a, packet.cmd);
+    assert!(packet.data.is_empty());
+
+    // the flag of a V2 frame tunneled in V1 is that of the inner frame
+    let inner = &frame[3..];
+    let mut tunneled = vec![b'$', b'M', b'<', inner.len() as u8, 0xff];
+    tunneled.extend_from_slice(inner);
+    tunneled.push(crc_v1(inner.len() as u8, 0xff, inner));
+    let packet = MspPacket::parse_one(&tunneled).unwrap();
+    assert_eq!(MspVersion::V2, packet.version);
+    assert_eq!(0x01, packet.flag);
+
+    assert_eq!(0, MspPacket::request(101).flag);
+}
+
+#[test]
+fn test_serialize_v2_flag() {
+    let packet = MspPacket::request(0x100a)
+        .with_payload(vec![0x24, 0x58])
+        .with_flag(0x01);
+    let expected = [
+        b'$',
+        b'X',
+        b'<',
+        0x01,
+        0x0a,
+        0x10,
+        0x02,
+        0x00,
+        0x24,
+        0x58,
+        crc_v2(0x01, 0x100a, &[0x24, 0x58]),
+    ];
+    assert_eq!(expected[10], packet.checksum_v2());
+
+    let mut output = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut output).unwrap();
+    assert_eq!(expected.to_vec(), output);
+    assert_eq!(packet, MspPacket::parse_one(&output).unwrap());
+
+    #[cfg(feature = "std")]
+    {
+        let mut written = vec![];
+        packet.write_v2(&mut written).unwrap();
+        assert_eq!(expected.to_vec(), written);
+    }
+}
+
+#[test]
+fn test_parse_slice() {
+    let packet = MspPacket::request(0x100a).with_payload(vec![0x24; 10]);
+    let mut frame = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut frame).unwrap();
+
+    let mut parser = MspParser::new();
+    assert_eq!(
+        Ok((vec![], MspSliceStatus::Incomplete { needed: 7 })),
+        parser.parse_slice(&frame[..12])
+    );
+    assert_eq!(
+        Ok((vec![packet.clone()], MspSliceStatus::Complete)),
+        parser.parse_slice(&frame[12..])
+    );
+
+    // the count is exact for every prefix past the payload length
+    for len in 8..frame.len() {
+        let mut parser = MspParser::new();
+        let (_, status) = parser.parse_slice(&frame[..len]).unwrap();
+        assert_eq!(
+            MspSliceStatus::Incomplete {
+                needed: frame.len() - len
+            },
+            status
+        );
+    }
+
+    // and a lower bound before it
+    for len in 1..8 {
+        let mut parser = MspParser::new();
+        parser.parse_slice(&frame[..len]).unwrap();
+        assert!(parser.bytes_needed() > 0);
+        assert!(parser.bytes_needed() <= frame.len() - len);
+    }
+}
+
+#[test]
+fn test_command_enum() {
+    assert_eq!(MspCommand::Status, MspPacket::request(101).command_enum());
+    assert_eq!(
+        MspCommand::Unknown(99),
+        MspPacket::request(99).command_enum()
+    );
+}
+
+#[test]
+fn test_parse_frame() {
+    let first = MspPacket::request(101);
+    let second = MspPacket::request(0x100a).with_payload(vec![0x24; 4]);
+    let mut input = vec![];
+    first.serialize_into(&mut input).unwrap();
+    let first_len = input.len();
+    input.extend(Vec::from(second.clone()));
+
+    let mut parser = MspParser::new();
+    let (packet, tail) = parser.parse_frame(&input).unwrap();
+    assert_eq!(Some(first), packet);
+    assert_eq!(&input[first_len..], tail);
+
+    let (packet, tail) = parser.parse_frame(tail).unwrap();
+    assert_eq!(Some(second), packet);
+    assert!(tail.is_empty());
+
+    let (packet, tail) = parser.parse_frame(&input[..3]).unwrap();
+    assert_eq!(None, packet);
+    assert!(tail.is_empty());
+    assert!(!parser.state_is_between_packets());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_packet_hash() {
+    use std::collections::HashSet;
+
+    let packets: HashSet<_> = [
+        MspPacket::request(101),
+        MspPacket::request(101),
+        MspPacket::request(101).with_payload(vec![1]),
+        MspPacket::request(101).with_flag(1),
+        MspPacket::request(101).with_direction(MspPacketDirection::FromFlightController),
+    ]
+    .iter()
+    .cloned()
+    .collect();
+    assert_eq!(4, packets.len());
+    assert!(packets.contains(&MspPacket::request(101)));
+}
+
+#[test]
+fn test_payload_len() {
+    assert_eq!(
+        3,
+        MspPacket::request(108)
+            .with_payload(vec![1, 2, 3])
+            .payload_len()
+    );
+    assert!(MspPacket::request(101).is_empty());
+}
+
+#[test]
+fn test_parse_all_lossy() {
+    let packets: Vec<_> = (0..4)
+        .map(|i| MspPacket::request(100 + i).with_payload(vec![i as u8; 3]))
+        .collect();
+    let mut input = vec![];
+    for packet in &packets {
+        let mut frame = vec![];
+        packet.serialize_into(&mut frame).unwrap();
+        input.extend(frame);
+    }
+    // corrupt the CRC of the third packet, and add noise after it
+    input[3 * 9 - 1] ^= 0xff;
+    input.splice(27..27, b"$M?".iter().cloned());
+
+    let mut parser = MspParser::new();
+    let parsed = parser.parse_all_lossy(&input);
+    assert_eq!(
+        vec![packets[0].clone(), packets[1].clone(), packets[3].clone()],
+        parsed
+    );
+    assert_eq!(
+        MspParserStats {
+            packets: 3,
+            crc_errors: 1,
+            framing_errors: 1,
+        },
+        parser.stats()
+    );
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn test_serialize_to_bytes() {
+    let packet = MspPacket::request(108).with_payload(vec![0x24, 0x4d, 0x3c]);
+
+    let mut v1 = vec![0; packet.packet_size_bytes()];
+    packet.serialize(&mut v1).unwrap();
+    assert_eq!(&v1[..], &packet.serialize_to_bytes().unwrap()[..]);
+
+    let mut v2 = vec![0; packet.packet_size_bytes_v2()];
+    packet.serialize_v2(&mut v2).unwrap();
+    assert_eq!(&v2[..], &packet.serialize_v2_to_bytes().unwrap()[..]);
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidDataLength),
+        packet.with_payload(vec![0; 256]).serialize_to_bytes()
+    );
+}
+
+#[test]
+fn test_take_payload() {
+    let mut packet = MspPacket::request(108).with_payload(vec![1, 2, 3]);
+    assert_eq!(vec![1, 2, 3], packet.take_payload());
+    assert!(packet.is_empty());
+    assert_eq!(108, packet.cmd);
+
+    let packet = packet.with_payload(vec![4, 5]);
+    assert_eq!(vec![4, 5], packet.into_payload());
+}
+
+#[test]
+fn test_strict_flags() {
+    let frame = [b'$', b'X', b'<', 0x01, 0x0a, 0x10, 0x00, 0x00, 0x72];
+
+    let mut parser = MspParser::new().with_strict_flags(true);
+    assert_eq!(
+        Err(MspPacketParseError::UnexpectedFlag { flag: 0x01 }),
+        parser.feed(&frame, |_| {})
+    );
+    assert!(parser.state_is_between_packets());
+
+    let mut v2 = vec![];
+    MspPacket::request(0x100a)
+        .serialize_v2_into(&mut v2)
+        .unwrap();
+    let mut parsed = 0;
+    parser.feed(&v2, |_| parsed += 1).unwrap();
+    assert_eq!(1, parsed);
+
+    // tunneled frames carry their flag in the V1 payload
+    let inner = &frame[3..];
+    let mut tunneled = vec![b'$', b'M', b'<', inner.len() as u8, 0xff];
+    tunneled.extend_from_slice(inner);
+    tunneled.push(crc_v1(inner.len() as u8, 0xff, inner));
+    let mut parsed = vec![];
+    parser.feed(&tunneled, |p| parsed.push(p)).unwrap();
+    assert_eq!(0x01, parsed[0].flag);
+
+    let mut parser = MspParser::new();
+    parser.feed(&frame, |_| {}).unwrap();
+}
+
+#[test]
+fn test_same_transaction() {
+    let request = MspPacket::request(108);
+    let response = MspPacket::request(108)
+        .with_direction(MspPacketDirection::FromFlightController)
+        .with_sequence(7);
+    assert!(request.same_transaction(&response));
+    assert!(request.clone().with_sequence(7).same_transaction(&response));
+    assert!(!request.clone().with_sequence(8).same_transaction(&response));
+    assert!(!MspPacket::request(101).same_transaction(&response));
+
+    let request = MspPacket::request(108).with_sequence(3);
+    let reply = MspPacket::reply_to(&request, vec![0x01]);
+    assert_eq!(3, reply.flag);
+    assert!(request.same_transaction(&reply));
+}
+
+#[test]
+fn test_parse_one_leading_noise() {
+    let mut input = vec![];
+    MspPacket::request(101).serialize_into(&mut input).unwrap();
+    input.splice(0..0, [0x00, 0x13]);
+
+    assert_eq!(
+        Err(MspPacketParseError::InvalidHeader1),
+        MspPacket::parse_one(&input)
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidHeader1),
+        MspPacket::validate_frame(&input)
+    );
+    assert_eq!(
+        Err(MspPacketParseError::InvalidHeader1),
+        MspPacket::try_from(&input[..])
+    );
+    assert_eq!(
+        Ok(MspPacket::request(101)),
+        MspPacket::parse_one(&input[2..])
+    );
 }
@@ -0,0 +1,210 @@
+//! Typed MSP message payloads
+
+use prelude::v1::*;
+
+use packet::MspPacketParseError;
+
+/// A message that can be decoded from, and encoded into, the payload of an MSP packet
+pub trait MspPayload: Sized {
+    /// Command code the message is sent with
+    const COMMAND: u16;
+
+    /// Decode the message from a packet's payload bytes
+    fn decode(data: &[u8]) -> Result<Self, MspPacketParseError>;
+
+    /// Encode the message into payload bytes
+    fn encode(&self) -> Result<Vec<u8>, MspPacketParseError>;
+}
+
+/// A message that can also be decoded without copying, into a type that borrows the payload.
+/// Implemented by the string heavy messages, where the owned decode has to allocate.
+pub trait MspBorrowedPayload: MspPayload {
+    /// The borrowing counterpart of the message
+    type Borrowed<'a>;
+
+    /// Decode the message from a packet's payload bytes, borrowing them
+    fn decode_borrowed(data: &[u8]) -> Result<Self::Borrowed<'_>, MspPacketParseError>;
+}
+
+/// Reads little-endian fields from the front of a payload, for use in `MspPayload::decode`.
+/// Every read fails with `InvalidData` when the payload is too short, leaving the reader where
+/// it was.
+#[derive(Debug, Clone)]
+pub struct PayloadReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PayloadReader<'a> {
+    /// Start reading at the beginning of `data`
+    pub fn new(data: &'a [u8]) -> PayloadReader<'a> {
+        PayloadReader { data }
+    }
+
+    /// Number of bytes not read yet
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Read the next `n` bytes
+    pub fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], MspPacketParseError> {
+        if self.data.len() < n {
+            return Err(MspPacketParseError::InvalidData);
+        }
+
+        let (bytes, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], MspPacketParseError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    /// Read the next `u8`
+    pub fn read_u8(&mut self) -> Result<u8, MspPacketParseError> {
+        self.read_array().map(u8::from_le_bytes)
+    }
+
+    /// Read the next `i8`
+    pub fn read_i8(&mut self) -> Result<i8, MspPacketParseError> {
+        self.read_array().map(i8::from_le_bytes)
+    }
+
+    /// Read the next `u16`
+    pub fn read_u16(&mut self) -> Result<u16, MspPacketParseError> {
+        self.read_array().map(u16::from_le_bytes)
+    }
+
+    /// Read the next `i16`
+    pub fn read_i16(&mut self) -> Result<i16, MspPacketParseError> {
+        self.read_array().map(i16::from_le_bytes)
+    }
+
+    /// Read the next `u32`
+    pub fn read_u32(&mut self) -> Result<u32, MspPacketParseError> {
+        self.read_array().map(u32::from_le_bytes)
+    }
+
+    /// Read the next `i32`
+    pub fn read_i32(&mut self) -> Result<i32, MspPacketParseError> {
+        self.read_array().map(i32::from_le_bytes)
+    }
+}
+
+/// Appends little-endian fields to a payload, for use in `MspPayload::encode`. The writes
+/// return the writer, so they can be chained.
+#[derive(Debug, Clone, Default)]
+pub struct PayloadWriter {
+    data: Vec<u8>,
+}
+
+impl PayloadWriter {
+    /// Start an empty payload
+    pub fn new() -> PayloadWriter {
+        PayloadWriter::default()
+    }
+
+    /// Start an empty payload with room for `capacity` bytes
+    pub fn with_capacity(capacity: usize) -> PayloadWriter {
+        PayloadWriter {
+            data: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Number of bytes written so far
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Has nothing been written yet?
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Finish the payload, returning its bytes
+    pub fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Append `bytes` as they are
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> &mut PayloadWriter {
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    /// Append a `u8`
+    pub fn write_u8(&mut self, value: u8) -> &mut PayloadWriter {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Append a `i8`
+    pub fn write_i8(&mut self, value: i8) -> &mut PayloadWriter {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Append a `u16`
+    pub fn write_u16(&mut self, value: u16) -> &mut PayloadWriter {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Append a `i16`
+    pub fn write_i16(&mut self, value: i16) -> &mut PayloadWriter {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Append a `u32`
+    pub fn write_u32(&mut self, value: u32) -> &mut PayloadWriter {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Append a `i32`
+    pub fn write_i32(&mut self, value: i32) -> &mut PayloadWriter {
+        self.write_bytes(&value.to_le_bytes())
+    }
+}
+
+#[test]
+fn test_payload_reader() {
+    let mut r = PayloadReader::new(&[0x01, 0xfe, 0xff, 0x02, 0x00, 0x00, 0x00, 0x03]);
+    assert_eq!(Ok(1), r.read_u8());
+    assert_eq!(Ok(-2), r.read_i16());
+    assert_eq!(Ok(2), r.read_i32());
+    assert_eq!(1, r.remaining());
+
+    // a failed read leaves the byte in place for the next one
+    assert_eq!(Err(MspPacketParseError::InvalidData), r.read_u16());
+    assert_eq!(Err(MspPacketParseError::InvalidData), r.read_i16());
+    assert_eq!(Err(MspPacketParseError::InvalidData), r.read_u32());
+    assert_eq!(Err(MspPacketParseError::InvalidData), r.read_i32());
+    assert_eq!(Err(MspPacketParseError::InvalidData), r.read_bytes(2));
+    assert_eq!(1, r.remaining());
+    assert_eq!(Ok(&[0x03][..]), r.read_bytes(1));
+
+    assert_eq!(0, r.remaining());
+    assert_eq!(Err(MspPacketParseError::InvalidData), r.read_u8());
+    assert_eq!(Err(MspPacketParseError::InvalidData), r.read_i8());
+    assert_eq!(Ok(&[][..]), r.read_bytes(0));
+}
+
+#[test]
+fn test_payload_writer() {
+    let mut w = PayloadWriter::with_capacity(16);
+    assert!(w.is_empty());
+    w.write_u8(0x01)
+        .write_i8(-1)
+        .write_u16(0x0302)
+        .write_i16(-2)
+        .write_u32(0x07060504)
+        .write_i32(-3)
+        .write_bytes(b"MSP");
+    assert_eq!(17, w.len());
+    assert_eq!(
+        vec![
+            0x01, 0xff, 0x02, 0x03, 0xfe, 0xff, 0x04, 0x05, 0x06, 0x07, 0xfd, 0xff, 0xff, 0xff,
+            b'M', b'S', b'P',
+        ],
+        w.into_vec()
+    );
+}
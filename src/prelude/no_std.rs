@@ -1,23 +1,25 @@
 pub use core::cell::RefCell;
 pub use core::cmp::*;
+pub use core::convert::TryFrom;
 pub use core::fmt;
 pub use core::fmt::Debug;
 pub use core::fmt::Error as FmtError;
 pub use core::fmt::Write as FmtWrite;
-pub use core::intrinsics::write_bytes;
 pub use core::iter;
 pub use core::marker::PhantomData;
 pub use core::mem;
+pub use core::mem::size_of;
 pub use core::num::Wrapping;
 pub use core::ops::Deref;
 pub use core::ops::Range;
+pub use core::ptr::write_bytes;
 
-pub use alloc::arc::Arc;
-pub use alloc::borrow::Cow;
+pub use alloc::borrow::{Cow, ToOwned};
 pub use alloc::boxed::Box;
 pub use alloc::fmt::format as format_to_string;
 pub use alloc::fmt::{Display, Formatter};
 pub use alloc::rc::Rc;
 pub use alloc::str::{from_utf8, FromStr};
 pub use alloc::string::*;
+pub use alloc::sync::Arc;
 pub use alloc::vec::Vec;
@@ -0,0 +1,150 @@
+//! Forwarding of packets between two streams, for sniffers and proxies sitting between a ground
+//! station and a flight controller
+
+use prelude::v1::*;
+use std::io::Read;
+
+use packet::{MspPacket, MspParser, MspVersion};
+
+/// Parse packets from `from` and write them to `to`, framed with the version they arrived in.
+/// V2 frames tunneled in V1 are tunneled again. Every packet is first passed to `inspect`, which
+/// returns the packet to forward, possibly rewritten, or `None` to drop it. Corrupt frames are
+/// dropped.
+///
+/// Runs until `from` reaches end of file; `WouldBlock`, `TimedOut` and `Interrupted` read errors
+/// are retried, any other I/O error is returned. A rewritten packet that no longer fits its
+/// frame, such as a V1 packet given a command above 255, fails with `InvalidInput`. Run one
+/// relay per direction to proxy a link.
+pub fn relay<R, W, F>(from: &mut R, to: &mut W, mut inspect: F) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(MspPacket) -> Option<MspPacket>,
+{
+    let mut parser = MspParser::new();
+    let mut buf = [0; 64];
+    loop {
+        let n = match from.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(ref e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        };
+
+        for b in &buf[..n] {
+            if let Ok(Some(packet)) = parser.parse(*b) {
+                let tunneled = parser.last_frame_tunneled();
+                if let Some(packet) = inspect(packet) {
+                    match packet.version {
+                        MspVersion::V2 if tunneled => packet
+                            .tunnel_v1()
+                            .map_err(|_| {
+                                io::Error::new(
+                                    io::ErrorKind::InvalidInput,
+                                    "packet does not fit in a tunneled V2 frame",
+                                )
+                            })?
+                            .write_v1(to)?,
+                        MspVersion::V1 => packet.write_v1(to)?,
+                        MspVersion::V2 => packet.write_v2(to)?,
+                    }
+                    to.flush()?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use packet::MspPacketDirection;
+
+#[test]
+fn test_relay() {
+    let requests = vec![
+        MspPacket::request(101),
+        MspPacket::request(0x1003),
+        MspPacket {
+            cmd: 214,
+            direction: MspPacketDirection::ToFlightController,
+            version: MspVersion::V1,
+            flag: 0,
+            data: vec![0xe8, 0x03, 0xe8, 0x03],
+        },
+    ];
+    let mut from_gcs = vec![0x00, 0x13];
+    for packet in &requests {
+        match packet.version {
+            MspVersion::V1 => packet.write_v1(&mut from_gcs).unwrap(),
+            MspVersion::V2 => packet.write_v2(&mut from_gcs).unwrap(),
+        }
+    }
+
+    // drop MSP_SET_MOTOR, and rewrite MSP_STATUS into MSP_STATUS_EX
+    let mut to_fc = vec![];
+    relay(&mut io::Cursor::new(from_gcs), &mut to_fc, |mut packet| {
+        match packet.cmd {
+            214 => return None,
+            101 => packet.cmd = 150,
+            _ => {}
+        }
+        Some(packet)
+    })
+    .unwrap();
+
+    let mut parsed = vec![];
+    MspParser::new().feed(&to_fc, |p| parsed.push(p)).unwrap();
+    assert_eq!(
+        vec![MspPacket::request(150), MspPacket::request(0x1003)],
+        parsed
+    );
+
+    // and back from the flight controller, preserving the versions
+    let responses: Vec<_> = parsed
+        .iter()
+        .map(|p| MspPacket::reply_to(p, vec![0x01]))
+        .collect();
+    let mut from_fc = vec![];
+    for packet in &responses {
+        match packet.version {
+            MspVersion::V1 => packet.write_v1(&mut from_fc).unwrap(),
+            MspVersion::V2 => packet.write_v2(&mut from_fc).unwrap(),
+        }
+    }
+    let mut to_gcs = vec![];
+    relay(&mut io::Cursor::new(from_fc.clone()), &mut to_gcs, Some).unwrap();
+    assert_eq!(from_fc, to_gcs);
+}
+
+#[test]
+fn test_relay_tunneled() {
+    let request = MspPacket::request(0x1f01).with_payload(vec![0x01, 0x02]);
+    let mut from_gcs = vec![];
+    request
+        .tunnel_v1()
+        .unwrap()
+        .write_v1(&mut from_gcs)
+        .unwrap();
+
+    let mut to_fc = vec![];
+    relay(&mut io::Cursor::new(from_gcs.clone()), &mut to_fc, Some).unwrap();
+    assert_eq!(from_gcs, to_fc);
+
+    // a V1 packet rewritten to a command that V1 cannot carry is refused
+    let mut from_gcs = vec![];
+    MspPacket::request(101).write_v1(&mut from_gcs).unwrap();
+    let error = relay(&mut io::Cursor::new(from_gcs), &mut vec![], |mut packet| {
+        packet.cmd = 0x1003;
+        Some(packet)
+    })
+    .unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, error.kind());
+}
@@ -0,0 +1,95 @@
+//! Serial port transport for `MspClient`, on top of the `serialport` crate
+
+use prelude::v1::*;
+use serialport::SerialPort;
+use std::io::Read;
+use std::time::Duration;
+
+/// Read timeout set on ports opened by `SerialMspTransport::open`. It is kept short, since
+/// `MspClient` applies its own timeouts between reads.
+pub const SERIAL_READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// A serial port, such as the USB port of a flight controller, usable as the transport of an
+/// `MspClient`:
+///
+/// ```no_run
+/// # use multiwii_serial_protocol_v2::*;
+/// # use std::time::Duration;
+/// let port = SerialMspTransport::open("/dev/ttyACM0", 115200).unwrap();
+/// let mut client = MspClient::new(
+///     port,
+///     MspVersion::V2,
+///     Duration::from_millis(100),
+///     Duration::from_secs(1),
+/// );
+/// let status = client.request(101).unwrap();
+/// ```
+pub struct SerialMspTransport {
+    port: Box<dyn SerialPort>,
+}
+
+impl SerialMspTransport {
+    /// Open the serial port at `path` with the given baud rate, 8N1 and no flow control
+    pub fn open(path: &str, baud_rate: u32) -> io::Result<SerialMspTransport> {
+        let port = serialport::new(path, baud_rate)
+            .timeout(SERIAL_READ_TIMEOUT)
+            .open()?;
+        Ok(SerialMspTransport { port })
+    }
+
+    /// Use a port that is already open, keeping its settings
+    pub fn from_port(port: Box<dyn SerialPort>) -> SerialMspTransport {
+        SerialMspTransport { port }
+    }
+
+    /// Consume the transport, returning the underlying port
+    pub fn into_inner(self) -> Box<dyn SerialPort> {
+        self.port
+    }
+}
+
+impl Read for SerialMspTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.port.read(buf)
+    }
+}
+
+impl Write for SerialMspTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+#[cfg(all(test, unix))]
+#[test]
+fn test_serial_transport() {
+    use client::MspClient;
+    use packet::{MspPacket, MspVersion};
+    use serialport::TTYPort;
+
+    let (master, mut slave) = TTYPort::pair().unwrap();
+    slave.set_timeout(SERIAL_READ_TIMEOUT).unwrap();
+
+    let response = MspPacket::reply_to(&MspPacket::request(101), vec![0x01]);
+    let mut frame = vec![];
+    response.serialize_into(&mut frame).unwrap();
+    slave.write_all(&frame).unwrap();
+
+    let mut transport = SerialMspTransport::from_port(Box::new(master));
+    transport.port.set_timeout(SERIAL_READ_TIMEOUT).unwrap();
+    let mut client = MspClient::new(
+        transport,
+        MspVersion::V1,
+        Duration::from_millis(100),
+        Duration::from_secs(1),
+    );
+    assert_eq!(Ok(response), client.request(101));
+
+    let mut request = [0; 6];
+    slave.read_exact(&mut request).unwrap();
+    assert_eq!([b'$', b'M', b'<', 0, 101, 101], request);
+}
@@ -0,0 +1,263 @@
+//! Asynchronous packet streams on top of `futures` IO traits
+
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::sink::Sink;
+use futures::stream::Stream;
+use prelude::v1::*;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use packet::{MspPacket, MspPacketParseError, MspParser, MspVersion};
+
+struct MspPacketStream<R> {
+    reader: R,
+    parser: MspParser,
+    buf: [u8; 64],
+    pos: usize,
+    len: usize,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> Stream for MspPacketStream<R> {
+    type Item = Result<MspPacket, MspPacketParseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            while this.pos < this.len {
+                let b = this.buf[this.pos];
+                this.pos += 1;
+
+                match this.parser.parse(b) {
+                    Ok(Some(packet)) => return Poll::Ready(Some(Ok(packet))),
+                    Ok(None) => {}
+                    Err(e) => return Poll::Ready(Some(Err(e))),
+                }
+            }
+
+            if this.eof {
+                return Poll::Ready(None);
+            }
+
+            match Pin::new(&mut this.reader).poll_read(cx, &mut this.buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => {
+                    this.eof = true;
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => {
+                    this.eof = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                Poll::Ready(Ok(n)) => {
+                    this.pos = 0;
+                    this.len = n;
+                }
+            }
+        }
+    }
+}
+
+/// A stream of every packet decoded from `reader`. Parse errors are yielded without ending the
+/// stream, which ends once the reader reaches EOF. A read error is yielded as `Io` and ends the
+/// stream.
+pub fn packet_stream<R: AsyncRead + Unpin>(
+    reader: R,
+) -> impl Stream<Item = Result<MspPacket, MspPacketParseError>> {
+    MspPacketStream {
+        reader,
+        parser: MspParser::new(),
+        buf: [0; 64],
+        pos: 0,
+        len: 0,
+        eof: false,
+    }
+}
+
+struct MspPacketSink<W> {
+    writer: W,
+    version: MspVersion,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<W: AsyncWrite + Unpin> MspPacketSink<W> {
+    fn poll_write_buf(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while self.pos < self.buf.len() {
+            match Pin::new(&mut self.writer).poll_write(cx, &self.buf[self.pos..]) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => self.pos += n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            }
+        }
+
+        self.buf.clear();
+        self.pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<MspPacket> for MspPacketSink<W> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_buf(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, packet: MspPacket) -> io::Result<()> {
+        let this = self.get_mut();
+        match this.version {
+            MspVersion::V1 => packet.write_v1(&mut this.buf),
+            MspVersion::V2 => packet.write_v2(&mut this.buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.writer).poll_flush(cx),
+            p => p,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.writer).poll_close(cx),
+            p => p,
+        }
+    }
+}
+
+/// A sink that serializes each packet with the given protocol version and writes it to
+/// `writer`. Flushing and closing the sink flush and close the writer.
+pub fn packet_sink<W: AsyncWrite + Unpin>(
+    writer: W,
+    version: MspVersion,
+) -> impl Sink<MspPacket, Error = io::Error> {
+    MspPacketSink {
+        writer,
+        version,
+        buf: Vec::new(),
+        pos: 0,
+    }
+}
+
+#[test]
+fn test_packet_stream() {
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::stream::StreamExt;
+    use packet::MspPacketDirection;
+
+    let packets: Vec<_> = (0..3)
+        .map(|i| MspPacket {
+            cmd: 100 + i,
+            direction: MspPacketDirection::FromFlightController,
+            version: MspVersion::V2,
+            flag: 0,
+            data: vec![i as u8; 40],
+        })
+        .collect();
+
+    let mut input = vec![];
+    for packet in &packets {
+        let mut output = vec![0; packet.packet_size_bytes_v2()];
+        packet.serialize_v2(&mut output).unwrap();
+        input.extend_from_slice(&output);
+    }
+    // corrupt the CRC of the second packet
+    let crc = packets[0].packet_size_bytes_v2() + packets[1].packet_size_bytes_v2() - 1;
+    input[crc] ^= 0xff;
+
+    let results: Vec<_> = block_on(packet_stream(Cursor::new(input)).collect());
+    assert_eq!(3, results.len());
+    assert_eq!(Ok(packets[0].clone()), results[0]);
+    match results[1] {
+        Err(MspPacketParseError::CrcMismatch { .. }) => {}
+        ref r => panic!("unexpected result {:?}", r),
+    }
+    assert_eq!(Ok(packets[2].clone()), results[2]);
+}
+
+#[test]
+fn test_packet_stream_read_error() {
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+
+    /// Hands out a single frame, then fails
+    struct Failing(Option<Vec<u8>>);
+
+    impl AsyncRead for Failing {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            match self.get_mut().0.take() {
+                Some(frame) => {
+                    buf[..frame.len()].copy_from_slice(&frame);
+                    Poll::Ready(Ok(frame.len()))
+                }
+                None => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+            }
+        }
+    }
+
+    let packet = MspPacket::request(101);
+    let mut frame = vec![];
+    packet.write_v1(&mut frame).unwrap();
+
+    let results: Vec<_> = block_on(packet_stream(Failing(Some(frame))).collect());
+    assert_eq!(
+        vec![
+            Ok(packet),
+            Err(MspPacketParseError::Io(io::ErrorKind::BrokenPipe))
+        ],
+        results
+    );
+}
+
+#[test]
+fn test_packet_relay() {
+    use futures::executor::block_on;
+    use futures::future::ready;
+    use futures::io::Cursor;
+    use futures::stream::{self, StreamExt};
+    use packet::MspPacketDirection;
+
+    let packets: Vec<_> = (0..3)
+        .map(|i| MspPacket {
+            cmd: 100 + i,
+            direction: MspPacketDirection::ToFlightController,
+            version: MspVersion::V1,
+            flag: 0,
+            data: vec![i as u8; 3],
+        })
+        .collect();
+
+    let mut input = vec![];
+    let mut expected = vec![];
+    for packet in &packets {
+        packet.write_v1(&mut input).unwrap();
+        packet.write_v2(&mut expected).unwrap();
+    }
+
+    // relay a V1 link onto a V2 link
+    let mut output = vec![];
+    let relayed = packet_stream(Cursor::new(input)).filter_map(|r| ready(r.ok()));
+    block_on(
+        relayed
+            .map(Ok)
+            .forward(packet_sink(&mut output, MspVersion::V2)),
+    )
+    .unwrap();
+    assert_eq!(expected, output);
+
+    let mut output = vec![];
+    block_on(stream::empty().forward(packet_sink(&mut output, MspVersion::V1))).unwrap();
+    assert!(output.is_empty());
+}